@@ -0,0 +1,369 @@
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::str::FromStr;
+use synop::{Command, FormSeparator, Synopsis};
+use error::Error;
+use diag;
+
+/// A command line option.
+pub struct OptSpec {
+    pub short: Option<char>,
+    pub long: &'static str,
+    /// The name of the argument, if the option takes one.
+    pub arg: Option<&'static str>,
+    pub help: &'static str
+}
+
+/// A description of a command, used to parse its arguments and print help.
+pub struct CmdSpec {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub about: &'static str,
+    /// Options specific to the command, in addition to the common ones.
+    pub opts: &'static [OptSpec]
+}
+
+const COMMON_OPTS: &[OptSpec] = &[
+    OptSpec { short: Some('e'), long: "expr", arg: Some("EXPR"),
+              help: "use EXPR as the synopsis instead of reading files" },
+    OptSpec { short: Some('o'), long: "output", arg: Some("FILE"),
+              help: "write the output to FILE instead of stdout" },
+    OptSpec { short: Some('p'), long: "paragraph", arg: None,
+              help: "separate forms by blank lines instead of newlines" },
+    OptSpec { short: Some('h'), long: "help", arg: None,
+              help: "print this help and exit" },
+    OptSpec { short: Some('V'), long: "version", arg: None,
+              help: "print the version and exit" }
+];
+
+/// A source of synopses.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Input {
+    Stdin,
+    File(String),
+    /// A synopsis given by `-e`.
+    Expr(String)
+}
+
+impl Input {
+    /// Returns the name used to refer to the input in messages.
+    pub fn name(&self) -> &str {
+        match *self {
+            Input::Stdin => "<stdin>",
+            Input::File(ref path) => path,
+            Input::Expr(_) => "<expr>"
+        }
+    }
+
+    fn read(&self, sep: FormSeparator, markup: Markup) -> io::Result<Synopsis> {
+        let mut src = String::new();
+        match *self {
+            Input::Stdin => { io::stdin().read_to_string(&mut src)?; }
+            Input::File(ref path) => { File::open(path)?.read_to_string(&mut src)?; }
+            Input::Expr(ref expr) => src.push_str(expr)
+        }
+        match markup {
+            Markup::Plain => Ok(Synopsis::parse(src, sep)),
+            Markup::Roff => Synopsis::from_roff(&src).ok_or_else(no_synopsis),
+            Markup::Mdoc => Synopsis::from_mdoc(&src).ok_or_else(no_synopsis)
+        }
+    }
+}
+
+fn no_synopsis() -> io::Error { io::Error::new(io::ErrorKind::InvalidData, "no SYNOPSIS section") }
+
+/// The markup synopses are written in.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Markup {
+    /// The plain bracket notation.
+    Plain,
+    /// A man(7) page, whose SYNOPSIS section is read.
+    Roff,
+    /// An mdoc(7) page, whose SYNOPSIS section is read.
+    Mdoc
+}
+
+impl FromStr for Markup {
+    type Err = ();
+
+    /// Parses the name of a markup: `text`, `roff` or `mdoc`.
+    fn from_str(s: &str) -> Result<Markup, ()> {
+        match s {
+            "text" => Ok(Markup::Plain),
+            "roff" => Ok(Markup::Roff),
+            "mdoc" => Ok(Markup::Mdoc),
+            _ => Err(())
+        }
+    }
+}
+
+/// Parsed command line arguments.
+#[derive(Debug)]
+pub struct Args {
+    name: &'static str,
+    pub inputs: Vec<Input>,
+    pub output: Option<String>,
+    pub separator: FormSeparator,
+    pub markup: Markup,
+    // Options specific to the command, by long name.
+    opts: Vec<(&'static str, Option<String>)>
+}
+
+impl Args {
+    /// Parses the arguments of the running process.  If `--help` or
+    /// `--version` is given, prints the message and returns `None`.
+    pub fn from_env(spec: &CmdSpec) -> Result<Option<Args>, Error> {
+        Args::parse(spec, env::args().skip(1))
+    }
+
+    /// Parses `args`, which do not include the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(spec: &CmdSpec, args: I) -> Result<Option<Args>, Error> {
+        let mut result = Args {
+            name: spec.name,
+            inputs: vec![],
+            output: None,
+            separator: FormSeparator::Line,
+            markup: Markup::Plain,
+            opts: vec![]
+        };
+        let mut only_files = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if only_files || arg == "-" || !arg.starts_with('-') {
+                result.inputs.push(if arg == "-" { Input::Stdin } else { Input::File(arg) });
+                continue
+            }
+            if arg == "--" {
+                only_files = true;
+                continue
+            }
+
+            let (opt, attached) = find_opt(spec, &arg)?;
+            let value = match (opt.arg, attached) {
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => match args.next() {
+                    Some(value) => Some(value),
+                    None => return Err(usage_error(spec.name, &format!("option `{}` requires an argument", arg)))
+                },
+                (None, Some(_)) => {
+                    return Err(usage_error(spec.name, &format!("option `--{}` takes no argument", opt.long)))
+                }
+                (None, None) => None
+            };
+            match opt.long {
+                "expr" => result.inputs.push(Input::Expr(value.unwrap())),
+                "output" => result.output = value,
+                "paragraph" => result.separator = FormSeparator::BlankLine,
+                "help" => {
+                    print!("{}", help(spec));
+                    return Ok(None)
+                }
+                "version" => {
+                    println!("{} {}", spec.name, spec.version);
+                    return Ok(None)
+                }
+                long => result.opts.push((long, value))
+            }
+        }
+        if result.inputs.is_empty() {
+            result.inputs.push(Input::Stdin);
+        }
+        Ok(Some(result))
+    }
+
+    /// Returns true if the command specific option `long` is given.
+    pub fn flag(&self, long: &str) -> bool { self.opts.iter().any(|&(name, _)| name == long) }
+
+    /// Returns the argument of the last occurrence of option `long`.
+    pub fn value(&self, long: &str) -> Option<&str> { self.values(long).pop() }
+
+    /// Returns the arguments of all occurrences of option `long`, in order.
+    pub fn values(&self, long: &str) -> Vec<&str> {
+        self.opts.iter()
+            .filter(|&&(name, _)| name == long)
+            .filter_map(|(_, value)| value.as_ref().map(|s| &s[..]))
+            .collect()
+    }
+
+    /// Parses the argument of the last occurrence of option `long`.
+    pub fn parse_value<T: FromStr>(&self, long: &str) -> Result<Option<T>, Error> {
+        match self.value(long) {
+            Some(value) => value.parse().map(Some).map_err(|_| self.invalid_value(long, value)),
+            None => Ok(None)
+        }
+    }
+
+    /// Returns the error for `value`, an invalid argument of option `long`.
+    pub fn invalid_value(&self, long: &str, value: &str) -> Error {
+        self.usage_error(&format!("invalid argument `{}` for option `--{}`", value, long))
+    }
+
+    /// Returns a usage error with `msg`, pointing to `--help`.
+    pub fn usage_error(&self, msg: &str) -> Error { usage_error(self.name, msg) }
+
+    /// Opens the output given by `-o`, or stdout.
+    pub fn open_output(&self) -> Result<Box<dyn Write>, Error> {
+        match self.output {
+            Some(ref path) => Ok(Box::new(io::BufWriter::new(File::create(path)?))),
+            None => Ok(Box::new(io::stdout()))
+        }
+    }
+
+    /// Calls `f` with each form of each input.  Forms which failed to parse
+    /// are reported to stderr and skipped.  Returns an error if any form
+    /// failed, or immediately if `f` fails or an input cannot be read.
+    pub fn for_each_form<F>(&self, mut f: F) -> Result<(), Error>
+        where F: FnMut(Command) -> Result<(), Error>
+    {
+        let mut total = 0;
+        let mut failed = 0;
+        for input in &self.inputs {
+            let Synopsis { src, forms } = match input.read(self.separator, self.markup) {
+                Ok(syn) => syn,
+                Err(err) => return Err(Error::Io(io::Error::new(err.kind(),
+                                                                format!("{}: {}", input.name(), err))))
+            };
+            total += forms.len();
+            for form in forms {
+                match form {
+                    Ok(cmd) => f(cmd)?,
+                    Err(err) => {
+                        failed += 1;
+                        ::report(&format!("{}:{}", input.name(), diag::describe_parse_error(&src, &err)));
+                    }
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(Error::Parse(format!("{} of {} forms failed to parse", failed, total)))
+        }
+        Ok(())
+    }
+}
+
+fn find_opt<'a>(spec: &'a CmdSpec, arg: &str) -> Result<(&'a OptSpec, Option<String>), Error> {
+    let all = || COMMON_OPTS.iter().chain(spec.opts.iter());
+    let found = if let Some(long) = arg.strip_prefix("--") {
+        let (name, value) = match long.find('=') {
+            Some(i) => (&long[..i], Some(long[i + 1..].to_string())),
+            None => (long, None)
+        };
+        all().find(|opt| opt.long == name).map(|opt| (opt, value))
+    } else {
+        let mut cs = arg[1..].chars();
+        let c = cs.next();
+        let rest = cs.as_str();
+        all().find(|opt| opt.short.is_some() && opt.short == c).and_then(|opt| {
+            match (opt.arg, rest.is_empty()) {
+                (_, true) => Some((opt, None)),
+                (Some(_), false) => Some((opt, Some(rest.to_string()))),
+                (None, false) => None
+            }
+        })
+    };
+    found.ok_or_else(|| usage_error(spec.name, &format!("unknown option `{}`", arg)))
+}
+
+fn usage_error(name: &str, msg: &str) -> Error {
+    Error::Usage(format!("{}\nTry `{} --help` for more information.", msg, name))
+}
+
+/// Returns the help message of the command.
+pub fn help(spec: &CmdSpec) -> String {
+    let mut buf = format!("Usage: {} [OPTIONS] [FILE...]\n\n{}\n\nOptions:\n", spec.name, spec.about);
+    let opts = spec.opts.iter().chain(COMMON_OPTS.iter())
+        .map(|opt| {
+            let short = match opt.short {
+                Some(c) => format!("-{}, ", c),
+                None => "    ".to_string()
+            };
+            let arg = match opt.arg {
+                Some(arg) => format!(" {}", arg),
+                None => "".to_string()
+            };
+            (format!("{}--{}{}", short, opt.long, arg), opt.help)
+        }).collect::<Vec<_>>();
+    let width = opts.iter().map(|(s, _)| s.len()).max().unwrap_or(0);
+    for (s, help) in opts {
+        buf.push_str(&format!("  {:2$}  {}\n", s, help, width));
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use synop::FormSeparator;
+    use super::{Args, CmdSpec, Input, OptSpec};
+
+    const SPEC: CmdSpec = CmdSpec {
+        name: "test",
+        version: "0.0.0",
+        about: "A test command.",
+        opts: &[
+            OptSpec { short: Some('n'), long: "limit", arg: Some("N"), help: "limit" },
+            OptSpec { short: None, long: "all", arg: None, help: "all" }
+        ]
+    };
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(&SPEC, args.iter().map(|s| s.to_string()))
+            .map(|args| args.unwrap())
+            .map_err(|e| e.to_string().lines().next().unwrap().to_string())
+    }
+
+    #[test]
+    fn inputs() {
+        assert_eq!(vec![Input::Stdin], parse(&[]).unwrap().inputs);
+        assert_eq!(vec![Input::File("a".to_string()), Input::Stdin, Input::Expr("ls".to_string())],
+                   parse(&["a", "-", "-e", "ls"]).unwrap().inputs);
+        assert_eq!(vec![Input::Expr("ls".to_string()), Input::Expr("cp".to_string()),
+                        Input::File("-o".to_string())],
+                   parse(&["-els", "--expr=cp", "--", "-o"]).unwrap().inputs);
+    }
+
+    #[test]
+    fn common_opts() {
+        let args = parse(&["-o", "out", "-p"]).unwrap();
+        assert_eq!(Some("out".to_string()), args.output);
+        assert_eq!(FormSeparator::BlankLine, args.separator);
+        let args = parse(&["--output=out"]).unwrap();
+        assert_eq!(Some("out".to_string()), args.output);
+        assert_eq!(FormSeparator::Line, args.separator);
+    }
+
+    #[test]
+    fn command_opts() {
+        let args = parse(&["-n", "3", "--limit=4", "--all"]).unwrap();
+        assert_eq!(Some("4"), args.value("limit"));
+        assert_eq!(vec!["3", "4"], args.values("limit"));
+        assert!(args.flag("all"));
+        let args = parse(&["-n5"]).unwrap();
+        assert_eq!(Some(5), args.parse_value::<u64>("limit").unwrap());
+        assert_eq!(None, args.parse_value::<u64>("all").unwrap());
+        assert!(!args.flag("all"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Some("unknown option `-x`".to_string()), parse(&["-x"]).err());
+        assert_eq!(Some("unknown option `--foo`".to_string()), parse(&["--foo"]).err());
+        assert_eq!(Some("unknown option `-px`".to_string()), parse(&["-px"]).err());
+        assert_eq!(Some("option `-n` requires an argument".to_string()), parse(&["-n"]).err());
+        assert_eq!(Some("option `--all` takes no argument".to_string()), parse(&["--all=1"]).err());
+        let err = parse(&["-nx"]).unwrap().parse_value::<u64>("limit").unwrap_err();
+        assert_eq!(Some("invalid argument `x` for option `--limit`"), err.to_string().lines().next());
+        let err = parse(&[]).unwrap().usage_error("`--all` is required");
+        assert_eq!("`--all` is required\nTry `test --help` for more information.", err.to_string());
+    }
+
+    #[test]
+    fn help() {
+        let help = super::help(&SPEC);
+        assert!(help.starts_with("Usage: test [OPTIONS] [FILE...]\n\nA test command.\n"));
+        assert!(help.contains("\n  -n, --limit N      limit\n"));
+        assert!(help.contains("\n      --all          all\n"));
+        assert!(help.contains("\n  -e, --expr EXPR    use EXPR"));
+    }
+}
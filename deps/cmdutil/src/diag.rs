@@ -0,0 +1,49 @@
+use synop::{ParseError, Span};
+
+/// Describes `err` occurred in `src`, followed by the offending source line
+/// with a caret under the error location.
+pub fn describe_parse_error(src: &str, err: &ParseError) -> String {
+    format!("{}: {}\n{}", err.span.lo, err, snippet(src, err.span))
+}
+
+/// Returns the source line where `span` starts, followed by a line with
+/// carets under the span.
+pub fn snippet(src: &str, span: Span) -> String {
+    let line = src.lines().nth(span.lo.line - 1).unwrap_or("");
+    // Keep tabs so that the caret lines up with the source line.
+    let mut indent = line.chars()
+        .take(span.lo.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    while indent.chars().count() < span.lo.column - 1 {
+        indent.push(' ');
+    }
+    let width = if span.hi.line == span.lo.line && span.hi.column > span.lo.column {
+        span.hi.column - span.lo.column
+    } else {
+        1
+    };
+    format!("{}\n{}{}", line, indent, "^".repeat(width))
+}
+
+#[cfg(test)]
+mod tests {
+    use synop;
+    use error::Error;
+
+    fn describe(s: &str) -> String {
+        Error::from(synop::read_ast(s.as_bytes()).unwrap_err()).to_string()
+    }
+
+    #[test]
+    fn describe_parse_error() {
+        assert_eq!("1:10: expected `]`, found EOF\ncmd [a |b\n         ^",
+                   describe("cmd [a |b"));
+        assert_eq!("2:5: expected `]`, found `}`\n\tb c}\n\t   ^",
+                   describe("cmd [a\n\tb c}\nd"));
+        assert_eq!("1:9: unexpected token `...` found\ncmd a | ...\n        ^^^",
+                   describe("cmd a | ..."));
+        assert_eq!("1:2: expected command name, found `-a`\n -a\n ^^",
+                   describe(" -a"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::error;
+use std::fmt;
+use std::io;
+use synop::{CompileError, ExpandError, ReadError};
+use diag;
+
+/// The exit status of a command, following the BSD `sysexits.h` convention.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ExitCode {
+    Success = 0,
+    /// A command line did not match the synopsis.
+    NoMatch = 1,
+    /// The command was called with invalid arguments.
+    Usage = 64,
+    /// The input could not be parsed or processed.
+    Parse = 65,
+    /// An error occurred while reading input or writing output.
+    Io = 74
+}
+
+/// An error which terminates a command.
+pub trait CmdError: fmt::Display {
+    fn exit_code(&self) -> ExitCode;
+
+    /// Returns true if the error is a write to a closed pipe, which happens
+    /// when the output is piped to a command like `head`.  Such errors are not
+    /// reported.
+    fn is_broken_pipe(&self) -> bool { false }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Usage(String),
+    /// A parse error.  The message includes the offending source line.
+    Parse(String),
+    /// The input is too large to process.
+    Expand(ExpandError),
+    /// The synopsis is too large to compile to an automaton.
+    Compile(CompileError),
+    /// Some of the inputs were rejected, as `grep` does when nothing matches.
+    NoMatch(String),
+    Io(io::Error)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Usage(ref msg) | Error::Parse(ref msg) | Error::NoMatch(ref msg) => {
+                write!(f, "{}", msg)
+            }
+            Error::Expand(ref err) => write!(f, "{}", err),
+            Error::Compile(ref err) => write!(f, "{}", err),
+            Error::Io(ref err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl CmdError for Error {
+    fn exit_code(&self) -> ExitCode {
+        match *self {
+            Error::Usage(_) => ExitCode::Usage,
+            Error::Parse(_) | Error::Expand(_) | Error::Compile(_) => ExitCode::Parse,
+            Error::NoMatch(_) => ExitCode::NoMatch,
+            Error::Io(_) => ExitCode::Io
+        }
+    }
+
+    fn is_broken_pipe(&self) -> bool {
+        match *self {
+            Error::Io(ref err) => err.kind() == io::ErrorKind::BrokenPipe,
+            _ => false
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error { Error::Io(err) }
+}
+
+impl From<ExpandError> for Error {
+    fn from(err: ExpandError) -> Error { Error::Expand(err) }
+}
+
+impl From<CompileError> for Error {
+    fn from(err: CompileError) -> Error { Error::Compile(err) }
+}
+
+impl From<ReadError> for Error {
+    fn from(err: ReadError) -> Error {
+        match err {
+            ReadError::Io(err) => Error::Io(err),
+            ReadError::Parse(err, src) => Error::Parse(diag::describe_parse_error(&src, &err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use synop;
+    use super::{CmdError, Error, ExitCode};
+
+    #[test]
+    fn exit_code() {
+        assert_eq!(ExitCode::Usage, Error::Usage("".to_string()).exit_code());
+        assert_eq!(ExitCode::Parse, Error::from(synop::read_ast("[".as_bytes()).unwrap_err()).exit_code());
+        let err = io::Error::other("oops");
+        assert_eq!(ExitCode::Io, Error::from(err).exit_code());
+        assert_eq!(65, ExitCode::Parse as i32);
+    }
+}
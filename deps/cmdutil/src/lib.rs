@@ -2,15 +2,42 @@
 #![crate_type = "lib"]
 #![warn(unused, bad_style, unused_qualifications)]
 
+extern crate synop;
+
+use std::env;
+use std::fmt;
 use std::io;
 use std::io::prelude::*;
-use std::fmt;
+use std::path::Path;
+use std::process;
+
+pub use args::{Args, CmdSpec, Input, Markup, OptSpec};
+pub use error::{CmdError, Error, ExitCode};
+pub use diag::{describe_parse_error, snippet};
+
+mod args;
+mod error;
+mod diag;
 
-pub fn main<E: fmt::Display, F: FnOnce() -> Result<(), E>>(f: F) {
-    match f() {
-        Ok(()) => {}
-        Err(msg) => {
-            let _ = writeln!(&mut io::stderr(), "{}", msg);
+/// Runs `f` as the body of a command.  If `f` fails, the error is reported to
+/// stderr and the process exits with the status corresponding to the error.
+pub fn main<E: CmdError, F: FnOnce() -> Result<(), E>>(f: F) {
+    if let Err(err) = f() {
+        if !err.is_broken_pipe() {
+            report(&err);
         }
+        process::exit(err.exit_code() as i32);
     }
 }
+
+/// Returns the name of the running program.
+pub fn prog_name() -> String {
+    env::args().next()
+        .and_then(|arg0| Path::new(&arg0).file_name().map(|s| s.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+/// Writes `msg` to stderr, prefixed with `prog: error: `.
+pub fn report<E: fmt::Display>(msg: &E) {
+    let _ = writeln!(&mut io::stderr(), "{}: error: {}", prog_name(), msg);
+}
@@ -1,45 +1,167 @@
+use std::mem;
+
 use token::Token;
-use ast::Expr::*;
+use span::Span;
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+
+/// A synopsis expression annotated with its location in the source text.
+///
+/// Spans do not take part in comparison; two expressions are equal if they
+/// have the same structure.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub node: ExprKind,
+    pub span: Span
+}
 
 #[derive(Eq, PartialEq, Debug, Clone)]
-pub enum Expr {
+pub enum ExprKind {
     Tok(Token),
+    /// An option (`ShortOpt` or `LongOpt`) and the argument it takes.
+    OptArg(Token, Token, ArgStyle),
     Seq(Vec<Expr>),
+    /// Elements written without whitespace between them, which form one word.
+    Concat(Vec<Expr>),
     Opt(Box<Expr>),
     Repeat(Box<Expr>),
-    Select(Vec<Expr>)
+    Select(Vec<Expr>),
+    /// Elements which may appear in any order, such as a run of optional
+    /// flags.  Written the same way as `Seq`.
+    Interleave(Vec<Expr>)
+}
+
+/// How an option argument is written.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ArgStyle {
+    /// `-o FILE`
+    Separate,
+    /// `--output=FILE`
+    Attached,
+    /// `--color[=WHEN]`
+    OptionalAttached
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Expr) -> bool { self.node == other.node }
+}
+
+impl Eq for Expr {}
+
+/// A synopsis form: the name of the program followed by its arguments.
+///
+/// As with `Expr`, the span does not take part in comparison.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: String,
+    pub args: Expr,
+    pub span: Span
+}
+
+impl PartialEq for Command {
+    fn eq(&self, other: &Command) -> bool { self.name == other.name && self.args == other.args }
+}
+
+impl Eq for Command {}
+
+impl Command {
+    #[inline]
+    pub fn new(name: String, args: Expr, span: Span) -> Command { Command { name, args, span } }
+
+    pub fn pretty(&self) -> String {
+        let args = match self.args.node {
+            Select(_) | OptArg(_, _, Separate) => format!("{{{}}}", self.args.pretty()),
+            _ => self.args.pretty()
+        };
+        if args.is_empty() { self.name.clone() } else { format!("{} {}", self.name, args) }
+    }
+
+    /// Infers unordered groups of options in the arguments.  See
+    /// `Expr::infer_unordered`.
+    pub fn infer_unordered(self) -> Command {
+        let Command { name, args, span } = self;
+        Command { name, args: args.infer_unordered(), span }
+    }
+
+    /// Normalizes the arguments.  A command without arguments has an empty
+    /// `Seq` as its arguments.
+    pub fn normalize(self) -> Command {
+        let Command { name, args, span } = self;
+        let empty = Span::empty(args.span.hi);
+        let args = args.normalize().unwrap_or_else(|| Expr::new(Seq(vec![]), empty));
+        Command { name, args, span }
+    }
+}
+
+impl From<ExprKind> for Expr {
+    fn from(node: ExprKind) -> Expr { Expr::new(node, Span::default()) }
 }
 
 impl Expr {
+    #[inline]
+    pub fn new(node: ExprKind, span: Span) -> Expr { Expr { node, span } }
+
     pub fn pretty(&self) -> String {
-        match *self {
+        match self.node {
             Tok(ref t) => t.pretty(),
-            Seq(ref s) => {
+            OptArg(ref opt, ref arg, style) => {
+                match style {
+                    Separate => format!("{} {}", opt.pretty(), arg.pretty()),
+                    Attached => format!("{}={}", opt.pretty(), arg.pretty()),
+                    OptionalAttached => format!("{}[={}]", opt.pretty(), arg.pretty())
+                }
+            }
+            Seq(ref s) | Interleave(ref s) => {
+                // A sequence of elements in any order reads back as a sequence.
+                let in_seq = matches!(self.node, Seq(_));
                 s.iter()
                     .map(|expr| {
                         let p = expr.pretty();
-                        match *expr {
-                            Tok(_) | Opt(_) | Repeat(_) => p,
-                            Seq(_) | Select(_) => format!("{{{}}}", p)
+                        match expr.node {
+                            Interleave(_) if in_seq => p,
+                            Tok(_) | OptArg(_, _, Attached) | OptArg(_, _, OptionalAttached) | Opt(_) | Repeat(_) |
+                            Concat(_) => p,
+                            // `-o FILE` outside a group reads back as a flag and an operand.
+                            OptArg(_, _, Separate) | Seq(_) | Select(_) | Interleave(_) => format!("{{{}}}", p)
                         }
                     }).collect::<Vec<String>>()
                     .join(" ")
             },
+            Concat(ref s) => {
+                let mut prev: Option<&Expr> = None;
+                let mut buf = String::new();
+                for expr in s {
+                    let p = expr.pretty();
+                    // Two tokens in a row would be read back as a single word.
+                    let after_tok = matches!(prev, Some(&Expr { node: Tok(_), .. }) | Some(&Expr { node: OptArg(..), .. }));
+                    match expr.node {
+                        Tok(_) if after_tok => buf.push_str(&format!("{{{}}}", p)),
+                        Tok(_) | OptArg(_, _, Attached) | OptArg(_, _, OptionalAttached) | Opt(_) => {
+                            buf.push_str(&p)
+                        }
+                        OptArg(_, _, Separate) | Seq(_) | Select(_) | Repeat(_) | Concat(_) | Interleave(_) => {
+                            buf.push_str(&format!("{{{}}}", p))
+                        }
+                    }
+                    prev = Some(expr);
+                }
+                buf
+            }
             Opt(ref e) => format!("[{}]", e.pretty()),
             Repeat(ref e) => {
                 let p = e.pretty();
-                match **e {
-                    Tok(_) | Opt(_) | Repeat(_) => format!("{}...", p),
-                    Seq(_) | Select(_) => format!("{{{}}}...", p),
+                match e.node {
+                    OptArg(_, _, Separate) | Seq(_) | Select(_) | Interleave(_) => format!("{{{}}}...", p),
+                    Tok(_) | OptArg(..) | Opt(_) | Repeat(_) | Concat(_) => format!("{}...", p)
                 }
             },
             Select(ref s) => {
                 s.iter()
                     .map(|expr| {
                         let p = expr.pretty();
-                        match *expr {
+                        match expr.node {
                             Select(_) => format!("{{{}}}", p),
-                            Tok(_) | Opt(_) | Repeat(_) | Seq(_) => p
+                            Tok(_) | OptArg(..) | Opt(_) | Repeat(_) | Seq(_) | Concat(_) | Interleave(_) => p
                         }
                     }).collect::<Vec<String>>()
                     .join(" | ")
@@ -48,50 +170,144 @@ impl Expr {
     }
 
     pub fn normalize(self) -> Option<Expr> {
-        match self {
-            Tok(_) => Some(self),
+        let Expr { node, span } = self;
+        match node {
+            Tok(_) | OptArg(..) => Some(Expr::new(node, span)),
             Seq(xs) => {
                 let mut v = xs.into_iter()
                     .filter_map(|x| x.normalize())
-                    .map(|x| match x { Seq(y) => y, _ => vec![x] })
-                    .flat_map(|xs| xs.into_iter())
+                    .flat_map(|x| match x.node { Seq(y) => y, _ => vec![x] })
                     .collect::<Vec<_>>();
                 match v.len() {
                     0 => None,
                     1 => Some(v.pop().unwrap()),
-                    _ => Some(Seq(v))
+                    _ => Some(Expr::new(Seq(v), span))
+                }
+            }
+            Concat(xs) => {
+                let mut v = xs.into_iter()
+                    .filter_map(|x| x.normalize())
+                    .flat_map(|x| match x.node { Concat(y) => y, _ => vec![x] })
+                    .collect::<Vec<_>>();
+                match v.len() {
+                    0 => None,
+                    1 => Some(v.pop().unwrap()),
+                    _ => Some(Expr::new(Concat(v), span))
+                }
+            }
+            Opt(x) => {
+                x.normalize()
+                    .map(|y| match y.node { Opt(z) => z, _ => Box::new(y) })
+                    .map(|y| Expr::new(Opt(y), span))
+            }
+            Repeat(x) => {
+                x.normalize()
+                    .map(|y| match y.node { Repeat(z) => z, _ => Box::new(y) })
+                    .map(|y| Expr::new(Repeat(y), span))
+            }
+            Interleave(xs) => {
+                let mut v = xs.into_iter().filter_map(|x| x.normalize()).collect::<Vec<_>>();
+                match v.len() {
+                    0 => None,
+                    1 => Some(v.pop().unwrap()),
+                    _ => Some(Expr::new(Interleave(v), span))
                 }
             }
-            Opt(x)    => x.normalize().map(|y| match y { Opt(z)    => z, _ => Box::new(y) }).map(Opt),
-            Repeat(x) => x.normalize().map(|y| match y { Repeat(z) => z, _ => Box::new(y) }).map(Repeat),
             Select(xs) => {
                 let mut has_opt = false;
                 let mut v = xs.into_iter()
                     .filter_map(|x| x.normalize())
-                    .map(|x| match x { Opt(y) => { has_opt = true; *y }, _ => x })
-                    .map(|x| match x { Select(y) => y, _ => vec![x] })
-                    .flat_map(|xs| xs.into_iter())
+                    .map(|x| match x.node { Opt(y) => { has_opt = true; *y }, _ => x })
+                    .flat_map(|x| match x.node { Select(y) => y, _ => vec![x] })
                     .collect::<Vec<_>>();
                 let sel = match v.len() {
                     0 => None,
                     1 => Some(v.pop().unwrap()),
-                    _ => Some(Select(v))
+                    _ => Some(Expr::new(Select(v), span))
                 };
-                if has_opt { sel.map(|x| Opt(Box::new(x))) } else { sel }
+                if has_opt { sel.map(|x| Expr::new(Opt(Box::new(x)), span)) } else { sel }
             }
         }
     }
+
+    /// Replaces each run of two or more optional options in a `Seq` with an
+    /// `Interleave`, as such options can usually be given in any order.
+    pub fn infer_unordered(self) -> Expr {
+        let Expr { node, span } = self;
+        let node = match node {
+            Tok(_) | OptArg(..) | Concat(_) => node,
+            Seq(xs) => {
+                let mut v = vec![];
+                let mut run = vec![];
+                for x in xs.into_iter().map(Expr::infer_unordered) {
+                    if x.is_optional_option() {
+                        run.push(x);
+                    } else {
+                        push_run(&mut v, &mut run);
+                        v.push(x);
+                    }
+                }
+                push_run(&mut v, &mut run);
+                if v.len() == 1 {
+                    return v.pop().unwrap()
+                }
+                Seq(v)
+            }
+            Opt(x) => Opt(Box::new(x.infer_unordered())),
+            Repeat(x) => Repeat(Box::new(x.infer_unordered())),
+            Select(xs) => Select(xs.into_iter().map(Expr::infer_unordered).collect()),
+            Interleave(xs) => Interleave(xs.into_iter().map(Expr::infer_unordered).collect())
+        };
+        Expr::new(node, span)
+    }
+
+    // Returns true if `self` is an optional option, such as `[-a]`, `[-o FILE]`
+    // or `[-v]...`.
+    fn is_optional_option(&self) -> bool {
+        match self.node {
+            Opt(ref e) => e.is_option(),
+            Repeat(ref e) => e.is_optional_option(),
+            _ => false
+        }
+    }
+
+    fn is_option(&self) -> bool {
+        match self.node {
+            Tok(ref tok) => tok.is_option(),
+            OptArg(..) => true,
+            Concat(ref xs) => xs.first().is_some_and(Expr::is_option),
+            Repeat(ref e) => e.is_option(),
+            Select(ref xs) => xs.iter().all(Expr::is_option),
+            Seq(_) | Opt(_) | Interleave(_) => false
+        }
+    }
+}
+
+// Moves the elements of `run` to `v`, as an `Interleave` if there are two or
+// more of them.
+fn push_run(v: &mut Vec<Expr>, run: &mut Vec<Expr>) {
+    if run.len() < 2 {
+        v.append(run);
+        return
+    }
+    let span = run[0].span.to(run[run.len() - 1].span);
+    v.push(Expr::new(Interleave(mem::take(run)), span));
 }
 
 #[cfg(test)]
 mod tests {
     use super::Expr;
-    use super::Expr::*;
+    use super::ExprKind::*;
     use parser;
     use token::Tokenizer;
-    use token::Token::Text;
+    use token::Token::{Text, ShortOpt};
 
-    fn text(s: &str) -> Expr { Tok(Text(s.to_string())) }
+    fn text(s: &str) -> Expr { Tok(Text(s.to_string())).into() }
+    fn seq(v: Vec<Expr>) -> Expr { Seq(v).into() }
+    fn opt(e: Expr) -> Expr { Opt(Box::new(e)).into() }
+    fn rep(e: Expr) -> Expr { Repeat(Box::new(e)).into() }
+    fn sel(v: Vec<Expr>) -> Expr { Select(v).into() }
+    fn cat(v: Vec<Expr>) -> Expr { Concat(v).into() }
 
     #[test]
     fn pretty_normalized() {
@@ -110,6 +326,26 @@ mod tests {
         check("a | b...");
         check("{a | b}...");
         check("[a] {a | b}...");
+        check("cp [-r] SOURCE... <dest>");
+        check("ls [--color[=WHEN]] [-o FILE] {-e PATTERN}... --output=FILE...");
+        check("-x[y] {a | b}file [a][b]... {-o FILE}[x] x{y}[z]a");
+        check("{a...}b {a b}c {a | b}... --out=X[Y]");
+    }
+
+    #[test]
+    fn command() {
+        fn check(expect: &str, s: &str) {
+            let parsed = parser::parse_command(Tokenizer::new(s.chars())).unwrap().normalize();
+            let pretty = parsed.pretty();
+            assert_eq!(expect.to_string(), pretty);
+            let reparsed = parser::parse_command(Tokenizer::new(pretty.chars())).unwrap();
+            assert_eq!(parsed, reparsed.normalize());
+        }
+        check("ls", "ls");
+        check("ls", "ls [] {}");
+        check("ls [-l] [FILE...]", "ls [-l] [FILE...]");
+        check("git {add | rm} FILE", "git {add|rm} FILE");
+        check("git {add | rm FILE}", "git add | rm FILE");
     }
 
     #[test]
@@ -118,17 +354,37 @@ mod tests {
             assert_eq!(result, input.normalize());
         }
 
-        check(Some(text("aa")), Seq(vec![text("aa")]));
-        check(None, Seq(vec![]));
-        check(None, Opt(Box::new(Seq(vec![]))));
-        check(None, Opt(Box::new(Opt(Box::new(Seq(vec![]))))));
-        check(None, Opt(Box::new(Opt(Box::new(Opt(Box::new(Seq(vec![]))))))));
-        check(Some(Repeat(Box::new(text("aa")))),
-              Repeat(Box::new(Repeat(Box::new(text("aa"))))));
-        check(Some(text("aa")), Select(vec![text("aa")]));
-        check(Some(Seq(vec![text("a"), text("b"), text("c")])),
-              Seq(vec![Seq(vec![text("a"), text("b")]), text("c")]));
-        check(Some(Repeat(Box::new(text("a")))),
-              Repeat(Box::new(Seq(vec![Repeat(Box::new(text("a")))]))));
+        check(Some(text("aa")), seq(vec![text("aa")]));
+        check(None, seq(vec![]));
+        check(None, opt(seq(vec![])));
+        check(None, opt(opt(seq(vec![]))));
+        check(None, opt(opt(opt(seq(vec![])))));
+        check(Some(rep(text("aa"))),
+              rep(rep(text("aa"))));
+        check(Some(text("aa")), sel(vec![text("aa")]));
+        check(Some(seq(vec![text("a"), text("b"), text("c")])),
+              seq(vec![seq(vec![text("a"), text("b")]), text("c")]));
+        check(Some(rep(text("a"))),
+              rep(seq(vec![rep(text("a"))])));
+        check(Some(text("a")), cat(vec![text("a"), opt(seq(vec![]))]));
+        check(Some(cat(vec![text("a"), text("b"), text("c")])),
+              cat(vec![cat(vec![text("a"), text("b")]), text("c")]));
+    }
+
+    #[test]
+    fn infer_unordered() {
+        fn check(expect: Expr, s: &str) {
+            let parsed = parser::parse(Tokenizer::new(s.chars())).unwrap().normalize().unwrap();
+            assert_eq!(expect, parsed.infer_unordered());
+        }
+        fn short(s: &str) -> Expr { Tok(ShortOpt(s.to_string())).into() }
+        fn il(v: Vec<Expr>) -> Expr { Interleave(v).into() }
+
+        check(seq(vec![il(vec![opt(short("a")), opt(short("b"))]), text("x")]), "[-a] [-b] x");
+        check(il(vec![opt(short("a")), rep(opt(short("v"))), opt(sel(vec![short("x"), short("y")]))]),
+              "[-a] [-v]... [-x | -y]");
+        check(seq(vec![opt(short("a")), text("x"), opt(short("b"))]), "[-a] x [-b]");
+        check(seq(vec![opt(text("a")), opt(short("b"))]), "[a] [-b]");
+        check(opt(seq(vec![text("x"), il(vec![opt(short("a")), opt(short("b"))])])), "[x [-a] [-b]]");
     }
 }
@@ -0,0 +1,550 @@
+//! Compilation of expressions to deterministic finite automata, which match a
+//! command line in time linear in its length.
+//!
+//! The alphabet of an automaton is made of the literal words of the expression
+//! (options and keywords), an "any operand" class, and the words matching each
+//! pattern written without spaces, such as `--output=FILE` or `-I{DIR}`.  A word
+//! matching several patterns takes the transitions of all of them.
+//!
+//! An `Interleave` of `n` elements compiles to `2^n` states or more, so
+//! `compile` fails on expressions too large, as given by `MAX_INTERLEAVE` and
+//! `MAX_STATES`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::slice;
+use token::Token::Placeholder;
+use ast::Expr;
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+use matcher;
+
+// A class of words labeling transitions of the NFA.
+#[derive(Eq, PartialEq, Debug, Clone)]
+enum Class {
+    Word(String),
+    /// An operand, with the name of its placeholder.
+    Operand(String),
+    /// A single word matching the expression.
+    Pattern(Expr)
+}
+
+// A nondeterministic automaton with epsilon transitions, labeled by the index
+// of a `Class`.
+struct Nfa {
+    classes: Vec<Class>,
+    edges: Vec<Vec<(Option<usize>, usize)>>
+}
+
+impl Nfa {
+    fn state(&mut self) -> usize {
+        self.edges.push(vec![]);
+        self.edges.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, label: Option<Class>, to: usize) {
+        let label = label.map(|class| {
+            match self.classes.iter().position(|c| *c == class) {
+                Some(i) => i,
+                None => {
+                    self.classes.push(class);
+                    self.classes.len() - 1
+                }
+            }
+        });
+        self.edges[from].push((label, to));
+    }
+
+    // Adds the states matching `expr` from `from`, and returns the final one.
+    fn build(&mut self, expr: &Expr, from: usize) -> usize {
+        match expr.node {
+            Tok(ref tok) => {
+                let to = self.state();
+                let class = match *tok {
+                    Placeholder(ref name) => Class::Operand(name.clone()),
+                    _ => Class::Word(tok.pretty())
+                };
+                self.edge(from, Some(class), to);
+                to
+            }
+            OptArg(ref opt, ref arg, Separate) => {
+                let mid = self.state();
+                let to = self.state();
+                let name = match *arg {
+                    Placeholder(ref name) => name.clone(),
+                    ref tok => tok.pretty()
+                };
+                self.edge(from, Some(Class::Word(opt.pretty())), mid);
+                self.edge(mid, Some(Class::Operand(name)), to);
+                to
+            }
+            OptArg(ref opt, _, style) => {
+                let to = self.state();
+                if style == OptionalAttached {
+                    self.edge(from, Some(Class::Word(opt.pretty())), to);
+                }
+                self.edge(from, Some(Class::Pattern(expr.clone())), to);
+                to
+            }
+            Concat(_) => {
+                let to = self.state();
+                self.edge(from, Some(Class::Pattern(expr.clone())), to);
+                if matcher::match_args(expr, &[]).is_some() {
+                    self.edge(from, None, to);
+                }
+                to
+            }
+            Seq(ref seq) => seq.iter().fold(from, |s, e| self.build(e, s)),
+            Opt(ref e) => {
+                let start = self.state();
+                self.edge(from, None, start);
+                let to = self.build(e, start);
+                self.edge(from, None, to);
+                to
+            }
+            Repeat(ref e) => {
+                let start = self.state();
+                self.edge(from, None, start);
+                let to = self.build(e, start);
+                self.edge(to, None, start);
+                to
+            }
+            Select(ref sel) => {
+                let to = self.state();
+                for e in sel {
+                    let start = self.state();
+                    self.edge(from, None, start);
+                    let end = self.build(e, start);
+                    self.edge(end, None, to);
+                }
+                to
+            }
+            Interleave(ref xs) => {
+                // A state for each set of elements matched so far.  Optional
+                // elements are only matched when not empty, so that skipping
+                // them does not make every state reachable from the first;
+                // a state goes to the end once the elements left can all be
+                // empty.
+                let nullable = xs.iter().map(|e| matcher::match_args(e, &[]).is_some()).collect::<Vec<_>>();
+                let end = self.state();
+                let mut states = HashMap::new();
+                states.insert(0u64, from);
+                let mut masks = vec![0u64];
+                while let Some(mask) = masks.pop() {
+                    let state = states[&mask];
+                    if (0..xs.len()).all(|i| mask & (1 << i) != 0 || nullable[i]) {
+                        self.edge(state, None, end);
+                    }
+                    for (i, e) in xs.iter().enumerate().filter(|&(i, _)| mask & (1 << i) == 0) {
+                        let e = match e.node {
+                            Opt(ref e) => e,
+                            _ => e
+                        };
+                        let start = self.state();
+                        self.edge(state, None, start);
+                        let last = self.build(e, start);
+                        let next = mask | (1 << i);
+                        let to = match states.get(&next) {
+                            Some(&s) => s,
+                            None => {
+                                masks.push(next);
+                                let s = self.state();
+                                states.insert(next, s);
+                                s
+                            }
+                        };
+                        self.edge(last, None, to);
+                    }
+                }
+                end
+            }
+        }
+    }
+
+    fn closure(&self, states: &mut BTreeSet<usize>) {
+        let mut stack = states.iter().cloned().collect::<Vec<_>>();
+        while let Some(s) = stack.pop() {
+            for &(label, to) in &self.edges[s] {
+                if label.is_none() && states.insert(to) {
+                    stack.push(to);
+                }
+            }
+        }
+    }
+}
+
+/// The most elements of an `Interleave`, which compiles to a state for each
+/// subset of them.
+pub const MAX_INTERLEAVE: usize = 16;
+/// The most states of an automaton before minimization.
+pub const MAX_STATES: usize = 1 << 17;
+/// The most patterns which may match the same word.  A word gets a symbol
+/// for each set of patterns it can match.
+pub const MAX_OVERLAP: usize = 8;
+
+/// The error returned when an expression is too large to compile.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum CompileError {
+    /// An `Interleave` has this many elements, more than `MAX_INTERLEAVE`.
+    Interleave(usize),
+    /// The automaton has more states than `MAX_STATES`.
+    States,
+    /// This many patterns, more than `MAX_OVERLAP`, may match the same word.
+    Patterns(usize)
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompileError::Interleave(n) => {
+                write!(f, "unordered group of {} elements is too large to compile (the limit is {})", n,
+                       MAX_INTERLEAVE)
+            }
+            CompileError::States => write!(f, "automaton exceeds the limit of {} states", MAX_STATES),
+            CompileError::Patterns(n) => {
+                write!(f, "{} patterns may match the same word (the limit is {})", n, MAX_OVERLAP)
+            }
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+/// A minimal deterministic automaton matching the arguments of a command.
+#[derive(Debug, Clone)]
+pub struct Dfa {
+    // The symbol of each literal word.
+    literals: HashMap<String, usize>,
+    patterns: Vec<Expr>,
+    // The symbol of a word by the indices of the patterns it matches and
+    // whether it is an operand.
+    pattern_symbols: HashMap<(Vec<usize>, bool), usize>,
+    operand: usize,
+    // The next state by the current state and the symbol.
+    trans: Vec<Vec<Option<usize>>>,
+    accept: Vec<bool>,
+    // The literal words and the placeholders written in the synopsis which
+    // can come next in each state.
+    expected: Vec<(Vec<String>, Vec<String>)>
+}
+
+/// Compiles `expr` to a minimal DFA.  Fails if the automaton would be too
+/// large, in which case `matcher::match_args` can match instead.
+pub fn compile(expr: &Expr) -> Result<Dfa, CompileError> {
+    let n = max_interleave(expr);
+    if n > MAX_INTERLEAVE {
+        return Err(CompileError::Interleave(n))
+    }
+    let mut nfa = Nfa { classes: vec![], edges: vec![] };
+    let start = nfa.state();
+    let accept = nfa.build(expr, start);
+
+    // Classes each symbol belongs to.
+    let mut symbols: Vec<Vec<usize>> = vec![];
+    let operand_classes = nfa.classes.iter().enumerate()
+        .filter(|&(_, c)| matches!(*c, Class::Operand(_)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let pattern_classes = nfa.classes.iter().enumerate()
+        .filter(|&(_, c)| matches!(*c, Class::Pattern(_)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let patterns = pattern_classes.iter().map(|&i| match nfa.classes[i] {
+        Class::Pattern(ref p) => p.clone(),
+        _ => unreachable!()
+    }).collect::<Vec<_>>();
+    let mut literals = HashMap::new();
+    for (i, class) in nfa.classes.iter().enumerate() {
+        if let Class::Word(ref w) = *class {
+            let mut v = vec![i];
+            if matcher::is_operand(w) {
+                v.extend(&operand_classes);
+            }
+            v.extend(pattern_classes.iter().zip(&patterns)
+                .filter(|&(_, p)| matcher::match_args(p, slice::from_ref(w)).is_some())
+                .map(|(&j, _)| j));
+            literals.insert(w.clone(), symbols.len());
+            symbols.push(v);
+        }
+    }
+    let operand = symbols.len();
+    symbols.push(operand_classes.clone());
+    let mut pattern_symbols = HashMap::new();
+    for group in overlapping(&patterns) {
+        if group.len() > MAX_OVERLAP {
+            return Err(CompileError::Patterns(group.len()))
+        }
+        for mask in 1..1usize << group.len() {
+            let matched = group.iter().enumerate()
+                .filter(|&(i, _)| mask & (1 << i) != 0)
+                .map(|(_, &p)| p)
+                .collect::<Vec<_>>();
+            for &is_operand in &[false, true] {
+                let mut v = matched.iter().map(|&p| pattern_classes[p]).collect::<Vec<_>>();
+                if is_operand {
+                    v.extend(&operand_classes);
+                }
+                pattern_symbols.insert((matched.clone(), is_operand), symbols.len());
+                symbols.push(v);
+            }
+        }
+    }
+    let mut class_symbols = vec![vec![]; nfa.classes.len()];
+    for (sym, classes) in symbols.iter().enumerate() {
+        for &c in classes {
+            class_symbols[c].push(sym);
+        }
+    }
+
+    // Subset construction.
+    let mut first = BTreeSet::new();
+    first.insert(start);
+    nfa.closure(&mut first);
+    let mut sets = vec![first.clone()];
+    let mut indices = HashMap::new();
+    indices.insert(first, 0);
+    let mut trans = vec![];
+    let mut i = 0;
+    while i < sets.len() {
+        let mut next = vec![BTreeSet::new(); symbols.len()];
+        for &s in &sets[i] {
+            for &(label, to) in &nfa.edges[s] {
+                for &sym in label.map_or(&[][..], |l| &class_symbols[l][..]) {
+                    next[sym].insert(to);
+                }
+            }
+        }
+        let mut row = vec![];
+        for mut set in next {
+            if set.is_empty() {
+                row.push(None);
+                continue
+            }
+            nfa.closure(&mut set);
+            let j = match indices.get(&set) {
+                Some(&j) => j,
+                None => {
+                    if sets.len() == MAX_STATES {
+                        return Err(CompileError::States)
+                    }
+                    indices.insert(set.clone(), sets.len());
+                    sets.push(set);
+                    sets.len() - 1
+                }
+            };
+            row.push(Some(j));
+        }
+        trans.push(row);
+        i += 1;
+    }
+    let accept = sets.iter().map(|s| s.contains(&accept)).collect();
+    let expected = sets.iter()
+        .map(|set| {
+            let mut words = BTreeSet::new();
+            let mut operands = BTreeSet::new();
+            for &(label, _) in set.iter().flat_map(|&s| &nfa.edges[s]) {
+                match label.map(|l| &nfa.classes[l]) {
+                    Some(Class::Word(w)) => { words.insert(w.clone()); }
+                    Some(Class::Operand(name)) => { operands.insert(name.clone()); }
+                    _ => {}
+                }
+            }
+            (words.into_iter().collect(), operands.into_iter().collect())
+        }).collect();
+    Ok(Dfa { literals, patterns, pattern_symbols, operand, trans, accept, expected }.minimize())
+}
+
+// Splits the indices of `patterns` into groups, so that no word matches
+// patterns of different groups.  Only the attached arguments of different
+// options are known not to match the same word.
+fn overlapping(patterns: &[Expr]) -> Vec<Vec<usize>> {
+    let disjoint = |a: &Expr, b: &Expr| match (&a.node, &b.node) {
+        (OptArg(x, _, _), OptArg(y, _, _)) => x != y,
+        _ => false
+    };
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for (i, p) in patterns.iter().enumerate() {
+        let (mut joined, rest): (Vec<_>, Vec<_>) = groups.into_iter()
+            .partition(|g| g.iter().any(|&j| !disjoint(p, &patterns[j])));
+        let mut group = joined.drain(..).flatten().collect::<Vec<_>>();
+        group.push(i);
+        group.sort();
+        groups = rest;
+        groups.push(group);
+    }
+    groups
+}
+
+fn max_interleave(expr: &Expr) -> usize {
+    match expr.node {
+        Tok(_) | OptArg(..) => 0,
+        Seq(ref xs) | Concat(ref xs) | Select(ref xs) => xs.iter().map(max_interleave).max().unwrap_or(0),
+        Opt(ref e) | Repeat(ref e) => max_interleave(e),
+        Interleave(ref xs) => xs.iter().map(max_interleave).fold(xs.len(), usize::max)
+    }
+}
+
+impl Dfa {
+    /// Returns the number of states.
+    pub fn state_count(&self) -> usize { self.trans.len() }
+
+    /// Returns true if `args` match the expression the automaton was compiled
+    /// from.
+    pub fn is_match(&self, args: &[String]) -> bool {
+        let mut state = Some(0);
+        for word in args {
+            state = state.and_then(|s| self.next(s, word));
+        }
+        state.is_some_and(|s| self.accept[s])
+    }
+
+    /// Returns the state reached from `state` by `word`, or `None` if `word`
+    /// cannot come next.  The start state is 0.
+    pub fn next(&self, state: usize, word: &str) -> Option<usize> {
+        self.symbol(word).and_then(|sym| self.trans[state][sym])
+    }
+
+    /// Returns the state reached from `state` by an operand not written in the
+    /// synopsis.
+    pub fn next_operand(&self, state: usize) -> Option<usize> { self.trans[state][self.operand] }
+
+    pub fn is_accepting(&self, state: usize) -> bool { self.accept[state] }
+
+    /// Returns the literal words which can come next in `state`, such as
+    /// options and keywords, sorted.
+    pub fn expected_words(&self, state: usize) -> &[String] { &self.expected[state].0 }
+
+    /// Returns the names of the placeholders which can come next in `state`,
+    /// sorted.
+    pub fn expected_operands(&self, state: usize) -> &[String] { &self.expected[state].1 }
+
+    /// Returns the literal words of the synopsis, in no particular order.
+    pub fn words(&self) -> Vec<&str> { self.literals.keys().map(|w| &w[..]).collect() }
+
+    /// Returns the patterns of the synopsis matching a single word, such as
+    /// `--output=FILE` or `-I{DIR}`.
+    pub fn patterns(&self) -> &[Expr] { &self.patterns }
+
+    fn symbol(&self, word: &str) -> Option<usize> {
+        if let Some(&sym) = self.literals.get(word) {
+            return Some(sym)
+        }
+        let operand = matcher::is_operand(word);
+        let arg = [word.to_string()];
+        let matched = (0..self.patterns.len())
+            .filter(|&i| matcher::match_args(&self.patterns[i], &arg).is_some())
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            return if operand { Some(self.operand) } else { None }
+        }
+        self.pattern_symbols.get(&(matched, operand)).cloned()
+    }
+
+    // Merges equivalent states by partition refinement.  The start state stays
+    // the first one.
+    fn minimize(self) -> Dfa {
+        let mut block = self.accept.iter().map(|&a| a as usize).collect::<Vec<_>>();
+        loop {
+            let mut signatures = HashMap::new();
+            let next = (0..self.trans.len())
+                .map(|s| {
+                    let sig = (block[s], self.trans[s].iter().map(|t| t.map(|t| block[t])).collect::<Vec<_>>());
+                    let count = signatures.len();
+                    *signatures.entry(sig).or_insert(count)
+                }).collect::<Vec<_>>();
+            let done = signatures.len() == block.iter().collect::<BTreeSet<_>>().len();
+            block = next;
+            if done {
+                break
+            }
+        }
+
+        // Blocks are numbered in order of their first state, so the start
+        // state remains 0.
+        let count = block.iter().max().map_or(0, |&b| b + 1);
+        let mut trans = vec![vec![]; count];
+        let mut accept = vec![false; count];
+        let mut expected = vec![(BTreeSet::new(), BTreeSet::new()); count];
+        for s in 0..self.trans.len() {
+            let b = block[s];
+            trans[b] = self.trans[s].iter().map(|t| t.map(|t| block[t])).collect();
+            accept[b] = self.accept[s];
+            expected[b].0.extend(self.expected[s].0.iter().cloned());
+            expected[b].1.extend(self.expected[s].1.iter().cloned());
+        }
+        let expected = expected.into_iter()
+            .map(|(w, o)| (w.into_iter().collect(), o.into_iter().collect()))
+            .collect();
+        Dfa { trans, accept, expected, ..self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use ast::Command;
+    use parser;
+    use matcher;
+
+    fn parse(s: &str) -> Command { parser::parse_command(Tokenizer::new(s.chars())).unwrap().normalize() }
+
+    fn argv(line: &str) -> Vec<String> { line.split_whitespace().map(|s| s.to_string()).collect() }
+
+    // Checks the automaton agrees with the backtracking matcher.
+    fn check(synopsis: &str, lines: &[&str], unordered: bool) {
+        let cmd = parse(synopsis);
+        let cmd = if unordered { cmd.infer_unordered() } else { cmd };
+        let dfa = super::compile(&cmd.args).unwrap();
+        for line in lines {
+            let args = argv(line);
+            assert_eq!(matcher::match_args(&cmd.args, &args).is_some(), dfa.is_match(&args),
+                       "`{}` against `{}`", line, synopsis);
+        }
+    }
+
+    #[test]
+    fn agrees_with_matcher() {
+        let lines = ["", "a", "-a", "-l", "-a -l", "-l -a", "-a a", "-a -l a b", "-o x a", "-o -x a",
+                     "--color", "--color=auto", "--color=", "add x", "rm x", "add add", "-Ix", "-I x",
+                     "x.c", "x-opt.c", "-v -v", "-a -a a", "-ox.c a", "-ox.c b", "-ox b", "--out=x"];
+        check("ls", &lines, false);
+        check("ls [-a] [-l] FILE...", &lines, false);
+        check("ls [-a] [-l] FILE...", &lines, true);
+        check("ls [-o FILE] [--color[=WHEN]] [FILE]...", &lines, false);
+        check("git {add | rm} FILE", &lines, false);
+        check("cc [-I{DIR}]... [FILE]", &lines, false);
+        check("cc NAME[-opt].c", &lines, false);
+        check("cmd {-a | -l | -v}... [FILE]", &lines, false);
+        check("cmd {-o{X} a | -o{Y}.c b}", &lines, false);
+        check("cmd [--out=FILE] [--out[=FILE] a] [-o{X}]", &lines, false);
+    }
+
+    #[test]
+    fn too_large() {
+        let flags = (0..20).map(|i| format!("[-{}]", i)).collect::<Vec<_>>();
+        let cmd = parse(&format!("cmd {}", flags.join(" "))).infer_unordered();
+        assert_eq!(Err(super::CompileError::Interleave(20)), super::compile(&cmd.args).map(|_| ()));
+        let cmd = parse(&format!("cmd {}", flags[..12].join(" "))).infer_unordered();
+        assert_eq!(1 << 12, super::compile(&cmd.args).unwrap().state_count());
+    }
+
+    #[test]
+    fn minimize() {
+        assert_eq!(3, super::compile(&parse("cmd {a b | a c}").args).unwrap().state_count());
+        assert_eq!(1, super::compile(&parse("cmd [a]...").args).unwrap().state_count());
+        assert_eq!(4, super::compile(&parse("cmd [-a] [-b]").infer_unordered().args).unwrap().state_count());
+    }
+
+    #[test]
+    fn expected() {
+        let dfa = super::compile(&parse("git [-v] {add FILE... | rm [-r] DIR}").args).unwrap();
+        assert_eq!(["-v", "add", "rm"], dfa.expected_words(0));
+        let s = dfa.next(0, "rm").unwrap();
+        assert_eq!(["-r"], dfa.expected_words(s));
+        assert_eq!(["DIR"], dfa.expected_operands(s));
+        assert!(!dfa.is_accepting(s));
+        assert!(dfa.is_accepting(dfa.next_operand(s).unwrap()));
+        assert_eq!(None, dfa.next(s, "-v"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Values bound to the options and placeholders of a matched command line.
+//!
+//! As in docopt, an element which can occur at most once binds a boolean (for
+//! flags and keywords) or a string (for placeholders and option arguments).
+//! An element which can occur more than once binds a count or a list instead.
+
+use std::collections::BTreeMap;
+use token::Token;
+use token::Token::{Placeholder, Equals};
+use ast::Expr;
+use ast::ExprKind::*;
+use matcher::Derivation;
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Value {
+    /// An absent placeholder or option argument.
+    Null,
+    Bool(bool),
+    Count(usize),
+    Str(String),
+    List(Vec<String>)
+}
+
+/// The values bound to each element, keyed by the element as written in the
+/// synopsis (e.g. `-v`, `--output` or `FILE`).
+pub type Bindings = BTreeMap<String, Value>;
+
+impl Value {
+    pub fn to_json(&self) -> String {
+        match *self {
+            Value::Null => "null".to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Count(n) => n.to_string(),
+            Value::Str(ref s) => json_str(s),
+            Value::List(ref v) => {
+                format!("[{}]", v.iter().map(|s| json_str(s)).collect::<Vec<_>>().join(", "))
+            }
+        }
+    }
+}
+
+/// Formats `bindings` as a JSON object.
+pub fn to_json(bindings: &Bindings) -> String {
+    let members = bindings.iter()
+        .map(|(k, v)| format!("{}: {}", json_str(k), v.to_json()))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", members.join(", "))
+}
+
+fn json_str(s: &str) -> String {
+    let mut buf = "\"".to_string();
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\t' => buf.push_str("\\t"),
+            c if (c as u32) < 0x20 => buf.push_str(&format!("\\u{:04x}", c as u32)),
+            c => buf.push(c)
+        }
+    }
+    buf.push('"');
+    buf
+}
+
+// What an element binds, and how many times it can occur in a command line
+// (`None` if unbounded).
+#[derive(Clone, Copy)]
+struct Slot {
+    takes_value: bool,
+    max: Option<usize>
+}
+
+/// Returns the values bound by `deriv`, a derivation of `expr`.  Elements of
+/// `expr` not taking part in the derivation are bound to `false`, `0`, `null`
+/// or `[]`.
+pub fn bind(expr: &Expr, deriv: &Derivation) -> Bindings {
+    let slots = slots(expr);
+    let mut occurrences = vec![];
+    collect(expr, deriv, &mut occurrences);
+    slots.into_iter()
+        .map(|(key, slot)| {
+            let values = occurrences.iter()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, v)| v.clone())
+                .collect::<Vec<Option<String>>>();
+            let value = match (slot.takes_value, slot.max == Some(1)) {
+                (false, true) => Value::Bool(!values.is_empty()),
+                (false, false) => Value::Count(values.len()),
+                (true, true) => match values.into_iter().next() {
+                    Some(Some(s)) => Value::Str(s),
+                    // `--color` given without its optional argument.
+                    Some(None) => Value::Bool(true),
+                    None => Value::Null
+                },
+                (true, false) => Value::List(values.into_iter().flatten().collect())
+            };
+            (key, value)
+        }).collect()
+}
+
+fn key(tok: &Token) -> Option<(String, bool)> {
+    match *tok {
+        Equals => None,
+        Placeholder(_) => Some((tok.pretty(), true)),
+        _ => Some((tok.pretty(), false))
+    }
+}
+
+fn slots(expr: &Expr) -> BTreeMap<String, Slot> {
+    let mut map = BTreeMap::new();
+    match expr.node {
+        Tok(ref tok) => {
+            if let Some((key, takes_value)) = key(tok) {
+                map.insert(key, Slot { takes_value, max: Some(1) });
+            }
+        }
+        OptArg(ref opt, _, _) => {
+            map.insert(opt.pretty(), Slot { takes_value: true, max: Some(1) });
+        }
+        Seq(ref seq) | Concat(ref seq) | Interleave(ref seq) => {
+            for e in seq {
+                merge(&mut map, slots(e), |a, b| a.and_then(|a| b.map(|b| a + b)));
+            }
+        }
+        Opt(ref e) => map = slots(e),
+        Repeat(ref e) => {
+            map = slots(e);
+            for slot in map.values_mut() {
+                slot.max = None;
+            }
+        }
+        Select(ref sel) => {
+            for e in sel {
+                merge(&mut map, slots(e), |a, b| a.and_then(|a| b.map(|b| a.max(b))));
+            }
+        }
+    }
+    map
+}
+
+fn merge<F>(map: &mut BTreeMap<String, Slot>, other: BTreeMap<String, Slot>, f: F)
+    where F: Fn(Option<usize>, Option<usize>) -> Option<usize>
+{
+    for (key, slot) in other {
+        let merged = match map.get(&key) {
+            Some(s) => {
+                Slot { takes_value: s.takes_value || slot.takes_value, max: f(s.max, slot.max) }
+            }
+            None => slot
+        };
+        map.insert(key, merged);
+    }
+}
+
+fn collect(expr: &Expr, deriv: &Derivation, out: &mut Vec<(String, Option<String>)>) {
+    match (&expr.node, deriv) {
+        (Tok(tok), Derivation::Tok(s)) => {
+            match key(tok) {
+                Some((key, true)) => out.push((key, Some(s.clone()))),
+                Some((key, false)) => out.push((key, None)),
+                None => {}
+            }
+        }
+        (OptArg(opt, _, _), Derivation::OptArg(arg)) => out.push((opt.pretty(), arg.clone())),
+        (Seq(seq), Derivation::Seq(ds)) | (Concat(seq), Derivation::Concat(ds)) => {
+            for (e, d) in seq.iter().zip(ds) {
+                collect(e, d, out);
+            }
+        }
+        (Opt(e), Derivation::Opt(Some(d))) => collect(e, d, out),
+        (Opt(_), Derivation::Opt(None)) => {}
+        (Repeat(e), Derivation::Repeat(ds)) => {
+            for d in ds {
+                collect(e, d, out);
+            }
+        }
+        (Select(sel), Derivation::Select(i, d)) => collect(&sel[*i], d, out),
+        (Interleave(xs), Derivation::Interleave(ds)) => {
+            for (i, d) in ds {
+                collect(&xs[*i], d, out);
+            }
+        }
+        _ => panic!("derivation does not follow the expression")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use parser;
+    use matcher;
+    use super::Value::*;
+    use super::Bindings;
+
+    fn bind(synopsis: &str, line: &str) -> Bindings {
+        let cmd = parser::parse_command(Tokenizer::new(synopsis.chars())).unwrap().normalize();
+        let argv = line.split_whitespace().map(|s| s.to_string()).collect::<Vec<_>>();
+        super::bind(&cmd.args, &matcher::match_command(&cmd, &argv).unwrap())
+    }
+
+    fn strs(v: &[&str]) -> Vec<String> { v.iter().map(|s| s.to_string()).collect() }
+
+    #[test]
+    fn values() {
+        let b = bind("cmd [-v]... [-o FILE] [-q] SRC... {add|rm}", "cmd -v -v a b rm");
+        assert_eq!(Some(&Count(2)), b.get("-v"));
+        assert_eq!(Some(&Null), b.get("-o"));
+        assert_eq!(Some(&Bool(false)), b.get("-q"));
+        assert_eq!(Some(&List(strs(&["a", "b"]))), b.get("SRC"));
+        assert_eq!(Some(&Bool(false)), b.get("add"));
+        assert_eq!(Some(&Bool(true)), b.get("rm"));
+        assert_eq!(6, b.len());
+
+        let b = bind("cmd [-o FILE] [--color[=WHEN]] [--ext=EXT]...", "cmd -o x --color --ext=a --ext=b");
+        assert_eq!(Some(&Str("x".to_string())), b.get("-o"));
+        assert_eq!(Some(&Bool(true)), b.get("--color"));
+        assert_eq!(Some(&List(strs(&["a", "b"]))), b.get("--ext"));
+    }
+
+    #[test]
+    fn occurrences() {
+        // A placeholder occurring twice in a form collects into a list.
+        let b = bind("cp SRC DEST", "cp a b");
+        assert_eq!(Some(&Str("a".to_string())), b.get("SRC"));
+        let b = bind("diff FILE FILE", "diff a b");
+        assert_eq!(Some(&List(strs(&["a", "b"]))), b.get("FILE"));
+        // But not one occurring in two branches.
+        let b = bind("cmd {add FILE | rm FILE}", "cmd rm x");
+        assert_eq!(Some(&Str("x".to_string())), b.get("FILE"));
+        // A flag followed by an operand does not take it as its argument.
+        let b = bind("cat -n FILE", "cat -n a");
+        assert_eq!(Some(&Bool(true)), b.get("-n"));
+        assert_eq!(Some(&Str("a".to_string())), b.get("FILE"));
+    }
+
+    #[test]
+    fn json() {
+        let b = bind("cmd [-v]... [--output=FILE] FILE...", "cmd -v -v --output=x a b");
+        assert_eq!(r#"{"--output": "x", "-v": 2, "FILE": ["a", "b"]}"#, super::to_json(&b));
+        assert_eq!(r#""a\"b\\c\n\u0001""#, super::json_str("a\"b\\c\n\u{1}"));
+    }
+}
@@ -0,0 +1,562 @@
+//! Generation of shell completion scripts.
+//!
+//! A script tracks the state of the `Dfa` of the synopsis over the words
+//! before the cursor, and completes the words which can come next in that
+//! state.  So an option already given is not suggested again when it cannot
+//! be repeated, and only one branch of a `{X|Y}` is suggested once the other
+//! is taken.  Placeholders classified as files or directories complete file
+//! names.
+//!
+//! Words matching a pattern of the synopsis, such as `--output=FILE`, are
+//! matched by a glob made from the pattern (`--output=*`).  Other words which
+//! are not operands, such as unknown options, leave the state unchanged.
+//!
+//! The zsh script is an `_arguments` spec.  `_arguments` completes the options
+//! and their arguments, excluding those in other branches of a `{X|Y}` than
+//! one given, and the state only completes keywords and operands.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::str::FromStr;
+use token::Token::Placeholder;
+use ast::Expr;
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+use automaton::{self, CompileError, Dfa};
+use expand::{self, PlaceholderKind};
+use matcher;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Shell, String> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell `{}`", s))
+        }
+    }
+}
+
+// What is completed in a state.
+struct Completion<'a> {
+    words: &'a [String],
+    files: bool,
+    dirs: bool
+}
+
+// A glob matching the words of a pattern: literal strings, and `None` for
+// `*`.
+type Glob = Vec<Option<String>>;
+
+// The transitions of a `Dfa`, in the form the scripts use.
+struct Table<'a> {
+    // `(state, word, next)` for each literal word.
+    moves: Vec<(usize, &'a str, usize)>,
+    // `(state, glob, next)` for each pattern, tried after the literal words.
+    pattern_moves: Vec<(usize, Glob, usize)>,
+    // The states moving to each state by an operand.
+    operand_moves: BTreeMap<usize, Vec<usize>>,
+    completions: Vec<Completion<'a>>
+}
+
+impl<'a> Table<'a> {
+    fn new(dfa: &'a Dfa) -> Table<'a> {
+        let mut words = dfa.words();
+        words.sort();
+        let globs = dfa.patterns().iter()
+            .map(glob)
+            .filter(|g| g.iter().any(Option::is_some))
+            .map(|g| {
+                let sample = g.iter().map(|p| p.as_ref().map_or("x", |s| &s[..])).collect::<String>();
+                (g, sample)
+            }).collect::<Vec<_>>();
+        let mut moves = vec![];
+        let mut pattern_moves = vec![];
+        let mut operand_moves = BTreeMap::new();
+        let mut completions = vec![];
+        for s in 0..dfa.state_count() {
+            for &w in &words {
+                // Words taken as operands are left to the default case.
+                match dfa.next(s, w) {
+                    Some(t) if !(matcher::is_operand(w) && dfa.next_operand(s) == Some(t)) => moves.push((s, w, t)),
+                    _ => {}
+                }
+            }
+            for (g, sample) in &globs {
+                match dfa.next(s, sample) {
+                    Some(t) if !(matcher::is_operand(sample) && dfa.next_operand(s) == Some(t)) => {
+                        pattern_moves.push((s, g.clone(), t))
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(t) = dfa.next_operand(s) {
+                operand_moves.entry(t).or_insert_with(Vec::new).push(s);
+            }
+            let kinds = dfa.expected_operands(s).iter().map(|name| expand::classify(name)).collect::<Vec<_>>();
+            let files = kinds.contains(&PlaceholderKind::File);
+            completions.push(Completion {
+                words: dfa.expected_words(s),
+                files,
+                dirs: !files && kinds.contains(&PlaceholderKind::Dir)
+            });
+        }
+        Table { moves, pattern_moves, operand_moves, completions }
+    }
+}
+
+// Returns a glob matching the words `pattern` matches, and maybe others.
+fn glob(pattern: &Expr) -> Glob {
+    let mut g = vec![];
+    match pattern.node {
+        // The option alone is a literal word.
+        OptArg(ref opt, _, OptionalAttached) => g.extend(vec![Some(format!("{}=", opt.pretty())), None]),
+        _ => glob_into(pattern, &mut g)
+    }
+    // Merge adjacent `*`.
+    g.dedup_by(|a, b| a.is_none() && b.is_none());
+    g
+}
+
+fn glob_into(expr: &Expr, g: &mut Glob) {
+    match expr.node {
+        Tok(Placeholder(_)) => g.push(None),
+        Tok(ref tok) => g.push(Some(tok.pretty())),
+        OptArg(ref opt, _, Separate) => g.push(Some(opt.pretty())),
+        OptArg(ref opt, _, Attached) => {
+            g.push(Some(format!("{}=", opt.pretty())));
+            g.push(None);
+        }
+        OptArg(ref opt, _, OptionalAttached) => {
+            g.push(Some(opt.pretty()));
+            g.push(None);
+        }
+        Seq(ref seq) | Concat(ref seq) => {
+            for e in seq {
+                glob_into(e, g);
+            }
+        }
+        Opt(_) | Repeat(_) | Select(_) | Interleave(_) => g.push(None)
+    }
+}
+
+// Quotes the literal parts of `glob` for bash and zsh, after `prefix`.
+fn quote_glob(prefix: &str, glob: &Glob) -> String {
+    let mut s = String::new();
+    let mut lit = prefix.to_string();
+    for part in glob {
+        match *part {
+            Some(ref l) => lit.push_str(l),
+            None => {
+                if !lit.is_empty() {
+                    s.push_str(&quote(&lit));
+                    lit.clear();
+                }
+                s.push('*');
+            }
+        }
+    }
+    if !lit.is_empty() {
+        s.push_str(&quote(&lit));
+    }
+    s
+}
+
+// Quotes `glob` for a fish `case`, which takes wildcards even when quoted.
+fn quote_glob_fish(prefix: &str, glob: &Glob) -> String {
+    let parts = glob.iter().map(|p| p.as_ref().map_or("*", |s| &s[..])).collect::<String>();
+    quote_fish(&format!("{}{}", prefix, parts))
+}
+
+// Returns `name` made fit for a shell function name.
+fn ident(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+// Quotes `s` for bash and zsh.
+fn quote(s: &str) -> String { format!("'{}'", s.replace('\'', "'\\''")) }
+
+// Quotes `s` for fish.
+fn quote_fish(s: &str) -> String { format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")) }
+
+fn join<T: ToString>(items: &[T], sep: &str) -> String {
+    items.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(sep)
+}
+
+/// Returns a script completing the arguments of the command `name`, whose
+/// forms are given by `expr`.  Fails if `expr` is too large to compile.
+pub fn script(shell: Shell, name: &str, expr: &Expr) -> Result<String, CompileError> {
+    let dfa = automaton::compile(expr)?;
+    let table = Table::new(&dfa);
+    Ok(match shell {
+        Shell::Bash => bash(name, &table),
+        Shell::Zsh => zsh(name, &table, expr),
+        Shell::Fish => fish(name, &table)
+    })
+}
+
+fn bash(name: &str, table: &Table) -> String {
+    let f = format!("_synop_{}", ident(name));
+    let mut s = String::new();
+    let _ = writeln!(s, "# bash completion for {}", name);
+    let _ = writeln!(s, "{}() {{", f);
+    let _ = writeln!(s, "    local cur=${{COMP_WORDS[COMP_CWORD]}} line=${{COMP_LINE:0:COMP_POINT}}");
+    let _ = writeln!(s, "    local words=() cword=-1 state=0 i blank");
+    // Readline also splits words at `=` and `:`, which `COMP_WORDBREAKS`
+    // holds, so the pieces written without whitespace between are joined.
+    let _ = writeln!(s, "    for ((i = 0; i <= COMP_CWORD; i++)); do");
+    let _ = writeln!(s, "        blank=${{line%%[![:space:]]*}}");
+    let _ = writeln!(s, "        line=${{line#\"$blank\"}}");
+    let _ = writeln!(s, "        if [[ -z $blank && $i -gt 0 ]]; then");
+    let _ = writeln!(s, "            words[cword]+=${{COMP_WORDS[i]}}");
+    let _ = writeln!(s, "        else");
+    let _ = writeln!(s, "            words[++cword]=${{COMP_WORDS[i]}}");
+    let _ = writeln!(s, "        fi");
+    let _ = writeln!(s, "        line=${{line:${{#COMP_WORDS[i]}}}}");
+    let _ = writeln!(s, "    done");
+    let _ = writeln!(s, "    for ((i = 1; i < cword; i++)); do");
+    let _ = writeln!(s, "        case $state:${{words[i]}} in");
+    for &(from, w, to) in &table.moves {
+        let _ = writeln!(s, "            {}) state={} ;;", quote(&format!("{}:{}", from, w)), to);
+    }
+    for &(from, ref g, to) in &table.pattern_moves {
+        let _ = writeln!(s, "            {}) state={} ;;", quote_glob(&format!("{}:", from), g), to);
+    }
+    let _ = writeln!(s, "            *) case ${{words[i]}} in");
+    let _ = writeln!(s, "                   -?* | '') ;;");
+    let _ = writeln!(s, "                   *) case $state in");
+    for (to, from) in &table.operand_moves {
+        let _ = writeln!(s, "                          {}) state={} ;;", join(from, " | "), to);
+    }
+    let _ = writeln!(s, "                      esac ;;");
+    let _ = writeln!(s, "               esac ;;");
+    let _ = writeln!(s, "        esac");
+    let _ = writeln!(s, "    done");
+    let _ = writeln!(s, "    COMPREPLY=()");
+    let _ = writeln!(s, "    case $state in");
+    for (state, c) in table.completions.iter().enumerate() {
+        let mut actions = vec![];
+        if !c.words.is_empty() {
+            let words = c.words.iter().map(|w| quote(w)).collect::<Vec<_>>();
+            actions.push(format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", join(&words, " ")));
+        }
+        if c.files || c.dirs {
+            let flag = if c.files { "-f" } else { "-d" };
+            actions.push(format!("COMPREPLY+=($(compgen {} -- \"$cur\"))", flag));
+        }
+        if !actions.is_empty() {
+            let _ = writeln!(s, "        {}) {} ;;", state, join(&actions, "; "));
+        }
+    }
+    let _ = writeln!(s, "    esac");
+    let _ = writeln!(s, "}}");
+    let _ = writeln!(s, "complete -F {} {}", f, quote(name));
+    s
+}
+
+// An option as `_arguments` describes it.
+#[derive(Default)]
+struct ZshOption {
+    repeated: bool,
+    // The spec of the argument, such as `=-:FILE:_files`.
+    arg: Option<String>,
+    // The options of the other branches of a `{X|Y}`.
+    excludes: BTreeSet<String>
+}
+
+// Adds the options of `expr` to `opts`.
+fn zsh_options(expr: &Expr, repeated: bool, opts: &mut BTreeMap<String, ZshOption>) {
+    let mut add = |opt: String, arg: Option<String>| {
+        let o = opts.entry(opt).or_default();
+        o.repeated |= repeated;
+        if o.arg.is_none() {
+            o.arg = arg;
+        }
+    };
+    match expr.node {
+        Tok(ref tok) if tok.is_option() => add(tok.pretty(), None),
+        Tok(_) => {}
+        OptArg(ref opt, ref arg, style) => {
+            let (sep, optional) = match style {
+                Separate => ("", false),
+                Attached => ("=-", false),
+                OptionalAttached => ("=-", true)
+            };
+            add(opt.pretty(), Some(format!("{}{}{}", sep, if optional { "::" } else { ":" }, zsh_action(arg.pretty()))));
+        }
+        // `-I{DIR}`
+        Concat(ref seq) => {
+            if let [Expr { node: Tok(ref opt), .. }, Expr { node: Tok(Placeholder(ref name)), .. }] = seq[..] {
+                if opt.is_option() {
+                    add(opt.pretty(), Some(format!("-:{}", zsh_action(name.clone()))));
+                }
+            }
+        }
+        Seq(ref seq) | Interleave(ref seq) => {
+            for e in seq {
+                zsh_options(e, repeated, opts);
+            }
+        }
+        Opt(ref e) => zsh_options(e, repeated, opts),
+        Repeat(ref e) => zsh_options(e, true, opts),
+        Select(ref sel) => {
+            let branches = sel.iter().map(|e| {
+                let mut branch = BTreeMap::new();
+                zsh_options(e, repeated, &mut branch);
+                branch
+            }).collect::<Vec<_>>();
+            let names = branches.iter().map(|b| b.keys().cloned().collect::<BTreeSet<_>>()).collect::<Vec<_>>();
+            for (i, branch) in branches.into_iter().enumerate() {
+                for (name, o) in branch {
+                    let others = names.iter().enumerate()
+                        .filter(|&(j, _)| j != i)
+                        .flat_map(|(_, n)| n.iter())
+                        .filter(|n| !names[i].contains(*n))
+                        .cloned();
+                    let entry = opts.entry(name).or_default();
+                    entry.repeated |= o.repeated;
+                    if entry.arg.is_none() {
+                        entry.arg = o.arg;
+                    }
+                    entry.excludes.extend(o.excludes);
+                    entry.excludes.extend(others);
+                }
+            }
+        }
+    }
+}
+
+// Returns the message and action completing the placeholder `name`.
+fn zsh_action(name: String) -> String {
+    let action = match expand::classify(&name) {
+        PlaceholderKind::File => "_files",
+        PlaceholderKind::Dir => "_files -/",
+        PlaceholderKind::Number | PlaceholderKind::Other => " "
+    };
+    format!("{}:{}", name, action)
+}
+
+fn zsh(name: &str, table: &Table, expr: &Expr) -> String {
+    let mut opts = BTreeMap::new();
+    zsh_options(expr, false, &mut opts);
+    let mut specs = opts.into_iter().map(|(name, o)| {
+        let excludes = if o.excludes.is_empty() {
+            String::new()
+        } else {
+            format!("({})", join(&o.excludes.into_iter().collect::<Vec<_>>(), " "))
+        };
+        let repeated = if o.repeated { "*" } else { "" };
+        quote(&format!("{}{}{}{}", excludes, repeated, name, o.arg.unwrap_or_default()))
+    }).collect::<Vec<_>>();
+    specs.push(quote("*: :->operands"));
+
+    let f = format!("_synop_{}", ident(name));
+    let mut s = String::new();
+    let _ = writeln!(s, "#compdef {}", name);
+    let _ = writeln!(s);
+    let _ = writeln!(s, "{}() {{", f);
+    let _ = writeln!(s, "    local context state state_descr line dfa=0 i");
+    let _ = writeln!(s, "    typeset -A opt_args");
+    let _ = writeln!(s, "    _arguments -C -S \\");
+    let _ = writeln!(s, "        {} && return", join(&specs, " \\\n        "));
+    let _ = writeln!(s, "    [[ $state = operands ]] || return 1");
+    let _ = writeln!(s, "    for ((i = 2; i < CURRENT; i++)); do");
+    let _ = writeln!(s, "        case $dfa:${{words[i]}} in");
+    for &(from, w, to) in &table.moves {
+        let _ = writeln!(s, "            ({}) dfa={} ;;", quote(&format!("{}:{}", from, w)), to);
+    }
+    for &(from, ref g, to) in &table.pattern_moves {
+        let _ = writeln!(s, "            ({}) dfa={} ;;", quote_glob(&format!("{}:", from), g), to);
+    }
+    let _ = writeln!(s, "            (*) case ${{words[i]}} in");
+    let _ = writeln!(s, "                    (-?*|'') ;;");
+    let _ = writeln!(s, "                    (*) case $dfa in");
+    for (to, from) in &table.operand_moves {
+        let _ = writeln!(s, "                            ({}) dfa={} ;;", join(from, "|"), to);
+    }
+    let _ = writeln!(s, "                        esac ;;");
+    let _ = writeln!(s, "                esac ;;");
+    let _ = writeln!(s, "        esac");
+    let _ = writeln!(s, "    done");
+    // `_arguments` completes the options.
+    let _ = writeln!(s, "    case $dfa in");
+    for (state, c) in table.completions.iter().enumerate() {
+        let mut actions = vec![];
+        let keywords = c.words.iter().filter(|w| matcher::is_operand(w)).map(|w| quote(w)).collect::<Vec<_>>();
+        if !keywords.is_empty() {
+            actions.push(format!("compadd -- {}", join(&keywords, " ")));
+        }
+        if c.files {
+            actions.push("_files".to_string());
+        } else if c.dirs {
+            actions.push("_files -/".to_string());
+        }
+        if !actions.is_empty() {
+            let _ = writeln!(s, "        ({}) {} ;;", state, join(&actions, "; "));
+        }
+    }
+    let _ = writeln!(s, "    esac");
+    let _ = writeln!(s, "}}");
+    let _ = writeln!(s);
+    let _ = writeln!(s, "if [ \"$funcstack[1]\" = \"_{}\" ]; then", ident(name));
+    let _ = writeln!(s, "    {} \"$@\"", f);
+    let _ = writeln!(s, "else");
+    let _ = writeln!(s, "    compdef {} {}", f, quote(name));
+    let _ = writeln!(s, "fi");
+    s
+}
+
+fn fish(name: &str, table: &Table) -> String {
+    let f = format!("__synop_{}_state", ident(name));
+    let mut s = String::new();
+    let _ = writeln!(s, "# fish completion for {}", name);
+    let _ = writeln!(s, "function {}", f);
+    let _ = writeln!(s, "    set -l state 0");
+    let _ = writeln!(s, "    for word in (commandline -opc)[2..-1]");
+    let _ = writeln!(s, "        switch \"$state:$word\"");
+    for &(from, w, to) in &table.moves {
+        let _ = writeln!(s, "            case {}", quote_fish(&format!("{}:{}", from, w)));
+        let _ = writeln!(s, "                set state {}", to);
+    }
+    for &(from, ref g, to) in &table.pattern_moves {
+        let _ = writeln!(s, "            case {}", quote_glob_fish(&format!("{}:", from), g));
+        let _ = writeln!(s, "                set state {}", to);
+    }
+    let _ = writeln!(s, "            case '*'");
+    let _ = writeln!(s, "                if string match -qr -- '^(-.+)?$' \"$word\"");
+    let _ = writeln!(s, "                    continue");
+    let _ = writeln!(s, "                end");
+    let _ = writeln!(s, "                switch $state");
+    for (to, from) in &table.operand_moves {
+        let _ = writeln!(s, "                    case {}", join(from, " "));
+        let _ = writeln!(s, "                        set state {}", to);
+    }
+    let _ = writeln!(s, "                end");
+    let _ = writeln!(s, "        end");
+    let _ = writeln!(s, "    end");
+    let _ = writeln!(s, "    echo $state");
+    let _ = writeln!(s, "end");
+    let _ = writeln!(s);
+    let _ = writeln!(s, "complete -c {} -f", quote_fish(name));
+    for (state, c) in table.completions.iter().enumerate() {
+        let cond = quote_fish(&format!("test ({}) = {}", f, state));
+        if !c.words.is_empty() {
+            let _ = writeln!(s, "complete -c {} -n {} -a {}", quote_fish(name), cond, quote_fish(&c.words.join(" ")));
+        }
+        if c.files {
+            let _ = writeln!(s, "complete -c {} -n {} -F", quote_fish(name), cond);
+        } else if c.dirs {
+            let _ = writeln!(s, "complete -c {} -n {} -a '(__fish_complete_directories)'", quote_fish(name), cond);
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use token::Tokenizer;
+    use parser;
+    use super::Shell;
+
+    fn script(shell: Shell, synopsis: &str) -> String {
+        let cmd = parser::parse_command(Tokenizer::new(synopsis.chars())).unwrap().normalize();
+        super::script(shell, &cmd.name, &cmd.args).unwrap()
+    }
+
+    #[test]
+    fn bash() {
+        let s = script(Shell::Bash, "git [-v] {add FILE | rm DIR [-f]}");
+        assert!(s.contains("'0:add') state="));
+        assert!(s.contains("0) COMPREPLY=($(compgen -W \"'-v' 'add' 'rm'\" -- \"$cur\")) ;;"));
+        assert!(s.contains("compgen -f"));
+        assert!(s.contains("compgen -d"));
+        assert!(s.ends_with("complete -F _synop_git 'git'\n"));
+    }
+
+    // Runs the bash script of `synopsis`, a form of `cc`, on `words`, split as
+    // readline does, with the cursor at the end of `line`.  Returns the
+    // completions.
+    fn complete_bash(synopsis: &str, line: &str, words: &[&str]) -> Option<String> {
+        let mut s = script(Shell::Bash, synopsis);
+        let words = words.iter().map(|w| format!("'{}'", w)).collect::<Vec<_>>();
+        s.push_str(&format!("COMP_LINE='{}'; COMP_POINT=${{#COMP_LINE}}\n", line));
+        s.push_str(&format!("COMP_WORDS=({}); COMP_CWORD={}\n", words.join(" "), words.len() - 1));
+        s.push_str("_synop_cc; echo \"${COMPREPLY[*]}\"\n");
+        // Skipped where bash is not installed.
+        let mut child = Command::new("bash").arg("--norc").arg("-s")
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().ok()?;
+        child.stdin.take().unwrap().write_all(s.as_bytes()).unwrap();
+        let out = child.wait_with_output().unwrap();
+        assert!(out.status.success());
+        Some(String::from_utf8(out.stdout).unwrap().trim_end().to_string())
+    }
+
+    #[test]
+    fn bash_word_breaks() {
+        let synopsis = "cc --out=FILE --def=NAME {add | rm}";
+        let out = match complete_bash(synopsis, "cc ", &["cc", ""]) {
+            Some(out) => out,
+            None => return
+        };
+        assert_eq!("", out);
+        assert_eq!(Some("".to_string()),
+                   complete_bash(synopsis, "cc --out=x ", &["cc", "--out", "=", "x", ""]));
+        let words = ["cc", "--out", "=", "x", "--def", "=", "a", ":", "b", ""];
+        assert_eq!(Some("add rm".to_string()), complete_bash(synopsis, "cc --out=x --def=a:b ", &words));
+        let words = [&words[..9], &["a"]].concat();
+        assert_eq!(Some("add".to_string()), complete_bash(synopsis, "cc --out=x --def=a:b a", &words));
+        // The word under the cursor is not taken as given.
+        assert_eq!(Some("".to_string()), complete_bash(synopsis, "cc --out=x --def=a:", &words[..8]));
+    }
+
+    #[test]
+    fn zsh() {
+        let s = script(Shell::Zsh, "git [-v] {add FILE | rm DIR [-f]}");
+        assert!(s.starts_with("#compdef git\n"));
+        assert!(s.contains("(0) compadd -- 'add' 'rm' ;;"));
+        assert!(s.contains("_files -/"));
+
+        let s = script(Shell::Zsh, "ls [-a | -l] [-o FILE] [--color[=WHEN]] [-I{DIR}] [-v]... [FILE]...");
+        assert!(s.contains("    _arguments -C -S \\\n        '--color=-::WHEN: ' \\\n"));
+        assert!(s.contains(" '(-l)-a' \\\n        '(-a)-l' "));
+        assert!(s.contains(" '-o:FILE:_files' "));
+        assert!(s.contains(" '-I-:DIR:_files -/' "));
+        assert!(s.contains(" '*-v' "));
+        assert!(s.contains(" '*: :->operands' && return\n"));
+    }
+
+    #[test]
+    fn patterns() {
+        let s = script(Shell::Bash, "cc [--out=FILE] [-I{DIR}]... NAME[-opt].c");
+        assert!(s.contains("            '0:--out='*) state="));
+        assert!(s.contains("            '0:-I'*) state="));
+        assert!(s.contains("            '0:'*'.c') state="));
+        let s = script(Shell::Fish, "cc [--out=FILE] NAME");
+        assert!(s.contains("            case '0:--out=*'\n"));
+    }
+
+    #[test]
+    fn fish() {
+        let s = script(Shell::Fish, "git [-v] {add FILE | rm DIR [-f]}");
+        assert!(s.contains("complete -c 'git' -f\n"));
+        assert!(s.contains("complete -c 'git' -n 'test (__synop_git_state) = 0' -a '-v add rm'\n"));
+        assert!(s.contains(" -F\n"));
+    }
+
+    #[test]
+    fn parse_shell() {
+        assert_eq!(Ok(Shell::Fish), "fish".parse());
+        assert!("csh".parse::<Shell>().is_err());
+    }
+}
@@ -0,0 +1,410 @@
+//! Covering sets of command lines: small sets in which every combination of
+//! `t` choices made while expanding a synopsis appears at least once.
+//!
+//! The choices are whether each `[X]` (and `--opt[=ARG]`) is present, which
+//! branch of each `{X|Y}` is taken, and how many times each `X...` is
+//! repeated.  Only the boundary counts are covered: `repeat_min`, one more,
+//! and `repeat_max` of the `ExpandOptions`.  A choice nested in `X...` is made
+//! once for all the repetitions, and the elements of an `Interleave` are
+//! written in order.
+
+use std::ops::Range;
+use token::Token::Equals;
+use ast::Expr;
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+use expand::{ExpandOptions, Word};
+
+// A choice point, and the choice of its parent under which it is made.
+struct Param {
+    values: usize,
+    parent: Option<(usize, Range<usize>)>
+}
+
+// A value chosen for each parameter, or `None` for those whose parent choice
+// excludes them.
+type Assignment = Vec<Option<usize>>;
+
+// A combination of choices.  A nested choice may be not made at all.
+type Tuple = Vec<(usize, Option<usize>)>;
+
+// Returns the body of a `Repeat` and how many times it is repeated, as in
+// `expand`.
+fn repeat_bounds<'a>(rep: &'a Expr, opts: &ExpandOptions) -> (&'a Expr, usize, usize) {
+    match rep.node {
+        Opt(ref body) => (body, opts.repeat_min.saturating_sub(1), opts.repeat_max.saturating_sub(1)),
+        _ => (rep, opts.repeat_min, opts.repeat_max)
+    }
+}
+
+// Returns the repetition counts covered between `min` and `max`, none if
+// `min` is greater.
+fn repeat_counts(min: usize, max: usize) -> Vec<usize> {
+    let mut counts = vec![];
+    for n in vec![Some(min), min.checked_add(1), Some(max)].into_iter().flatten() {
+        if min <= n && n <= max && !counts.contains(&n) {
+            counts.push(n);
+        }
+    }
+    counts
+}
+
+// Adds the choice points of `expr`, in preorder.
+fn params(expr: &Expr, opts: &ExpandOptions, parent: Option<(usize, Range<usize>)>, out: &mut Vec<Param>) {
+    match expr.node {
+        Tok(_) => {}
+        OptArg(_, _, OptionalAttached) => out.push(Param { values: 2, parent }),
+        OptArg(..) => {}
+        Seq(ref seq) | Concat(ref seq) | Interleave(ref seq) => {
+            for e in seq {
+                params(e, opts, parent.clone(), out);
+            }
+        }
+        Opt(ref e) => {
+            out.push(Param { values: 2, parent });
+            let p = out.len() - 1;
+            params(e, opts, Some((p, 1..2)), out);
+        }
+        Repeat(ref rep) => {
+            let (body, min, max) = repeat_bounds(rep, opts);
+            let values = repeat_counts(min, max).len();
+            out.push(Param { values, parent });
+            let p = out.len() - 1;
+            // The body is expanded unless it is repeated zero times.
+            let first = if min == 0 { 1 } else { 0 };
+            params(body, opts, Some((p, first..values)), out);
+        }
+        Select(ref sel) => {
+            out.push(Param { values: sel.len(), parent });
+            let p = out.len() - 1;
+            for (i, e) in sel.iter().enumerate() {
+                params(e, opts, Some((p, i..i + 1)), out);
+            }
+        }
+    }
+}
+
+// Returns true if the choice `p` is made, given the choices of its ancestors.
+fn is_active(ps: &[Param], test: &Assignment, p: usize) -> bool {
+    match ps[p].parent {
+        Some((q, ref range)) => test[q].is_some_and(|v| range.contains(&v)),
+        None => true
+    }
+}
+
+// Returns the values of the choices of `tuple` and of their ancestors which
+// make all the choices of `tuple`, or `None` if they exclude each other.
+fn solve(ps: &[Param], tuple: &[(usize, Option<usize>)]) -> Option<Vec<Option<Option<usize>>>> {
+    let mut fixed = vec![None; ps.len()];
+    for &(p, v) in tuple {
+        fixed[p] = Some(v);
+    }
+    let mut relevant = vec![false; ps.len()];
+    for &(p, _) in tuple {
+        let mut next = Some(p);
+        while let Some(q) = next {
+            relevant[q] = true;
+            next = ps[q].parent.as_ref().map(|&(r, _)| r);
+        }
+    }
+    let order = (0..ps.len()).filter(|&p| relevant[p]).collect::<Vec<_>>();
+    let mut test = vec![None; ps.len()];
+    if !search(ps, &order, &fixed, &mut test) {
+        return None
+    }
+    Some((0..ps.len()).map(|p| if relevant[p] { Some(test[p]) } else { None }).collect())
+}
+
+// Assigns the parameters of `order`, preceded by their ancestors, by
+// backtracking.
+fn search(ps: &[Param], order: &[usize], fixed: &[Option<Option<usize>>], test: &mut Assignment) -> bool {
+    let (&p, rest) = match order.split_first() {
+        Some(x) => x,
+        None => return true
+    };
+    let candidates = match (is_active(ps, test, p), fixed[p]) {
+        (true, Some(Some(v))) => vec![Some(v)],
+        (true, Some(None)) | (false, Some(Some(_))) => vec![],
+        (true, None) => (0..ps[p].values).map(Some).collect(),
+        (false, _) => vec![None]
+    };
+    for v in candidates {
+        test[p] = v;
+        if search(ps, rest, fixed, test) {
+            return true
+        }
+    }
+    false
+}
+
+// Returns all the feasible combinations of `t` choices.
+fn tuples(ps: &[Param], t: usize) -> Vec<Tuple> {
+    let domains = ps.iter()
+        .map(|p| {
+            let mut d = (0..p.values).map(Some).collect::<Vec<_>>();
+            if p.parent.is_some() {
+                d.push(None);
+            }
+            d
+        }).collect::<Vec<_>>();
+    let mut out = vec![];
+    let mut combo = (0..t).collect::<Vec<_>>();
+    loop {
+        // Each combination of values of the parameters in `combo`.
+        let mut values = vec![0; t];
+        if combo.iter().all(|&p| !domains[p].is_empty()) {
+            loop {
+                let tuple = combo.iter().zip(&values).map(|(&p, &i)| (p, domains[p][i])).collect::<Tuple>();
+                if solve(ps, &tuple).is_some() {
+                    out.push(tuple);
+                }
+                match (0..t).find(|&i| values[i] + 1 < domains[combo[i]].len()) {
+                    Some(i) => {
+                        values[i] += 1;
+                        for v in &mut values[..i] {
+                            *v = 0;
+                        }
+                    }
+                    None => break
+                }
+            }
+        }
+        // The next combination of parameters, in lexicographic order.
+        match (0..t).rev().find(|&i| combo[i] < ps.len() - t + i) {
+            Some(i) => {
+                combo[i] += 1;
+                for j in i + 1..t {
+                    combo[j] = combo[j - 1] + 1;
+                }
+            }
+            None => return out
+        }
+    }
+}
+
+fn covers(test: &Assignment, tuple: &[(usize, Option<usize>)]) -> bool {
+    tuple.iter().all(|&(p, v)| test[p] == v)
+}
+
+/// Returns command lines matching `expr`, in which every combination of
+/// `strength` choices appears at least once.  Strength 2 gives pairwise
+/// coverage.
+///
+/// The set is built greedily, so it is small but not always the smallest.
+pub fn cover(expr: &Expr, strength: usize, opts: &ExpandOptions) -> Vec<Vec<Word>> {
+    let mut ps = vec![];
+    params(expr, opts, None, &mut ps);
+    // Repetition bounds which exclude each other leave no command line.
+    if ps.iter().any(|p| p.values == 0) {
+        return vec![]
+    }
+    let mut uncovered = tuples(&ps, strength.min(ps.len()));
+
+    let mut tests: Vec<Assignment> = vec![];
+    while !uncovered.is_empty() || tests.is_empty() {
+        // Start from the first combination not covered yet.
+        let fixed = match uncovered.first() {
+            Some(tuple) => solve(&ps, tuple).unwrap(),
+            None => vec![None; ps.len()]
+        };
+        let mut test = vec![None; ps.len()];
+        for p in 0..ps.len() {
+            if let Some(v) = fixed[p] {
+                test[p] = v;
+                continue
+            }
+            if !is_active(&ps, &test, p) {
+                continue
+            }
+            // The value completing the most uncovered combinations so far.
+            test[p] = (0..ps[p].values).max_by_key(|&v| {
+                let gain = uncovered.iter()
+                    .filter(|tuple| {
+                        tuple.iter().all(|&(q, w)| if q == p { w == Some(v) } else { q < p && test[q] == w })
+                    }).count();
+                // The smallest value among the best.
+                (gain, !v)
+            });
+        }
+        uncovered.retain(|tuple| !covers(&test, tuple));
+        tests.push(test);
+    }
+
+    tests.iter()
+        .map(|test| {
+            let mut words = vec![];
+            emit(expr, opts, test, &mut 0, &mut words);
+            words
+        }).collect()
+}
+
+// Returns the number of choice points in `expr`.
+fn param_count(expr: &Expr, opts: &ExpandOptions) -> usize {
+    let mut ps = vec![];
+    params(expr, opts, None, &mut ps);
+    ps.len()
+}
+
+// Writes the words `expr` expands to under `test`, whose parameters from `*p`
+// on are those of `expr`.
+fn emit(expr: &Expr, opts: &ExpandOptions, test: &Assignment, p: &mut usize, words: &mut Vec<Word>) {
+    match expr.node {
+        Tok(ref tok) => words.push(vec![tok.clone()]),
+        OptArg(ref opt, ref arg, style) => {
+            let attached = match style {
+                OptionalAttached => {
+                    *p += 1;
+                    test[*p - 1] == Some(1)
+                }
+                _ => style == Attached
+            };
+            if attached {
+                words.push(vec![opt.clone(), Equals, arg.clone()]);
+            } else if style == Separate {
+                words.push(vec![opt.clone()]);
+                words.push(vec![arg.clone()]);
+            } else {
+                words.push(vec![opt.clone()]);
+            }
+        }
+        Seq(ref seq) | Interleave(ref seq) => {
+            for e in seq {
+                emit(e, opts, test, p, words);
+            }
+        }
+        Concat(ref seq) => {
+            let mut parts = vec![];
+            for e in seq {
+                emit(e, opts, test, p, &mut parts);
+            }
+            let word = parts.into_iter().flatten().collect::<Word>();
+            if !word.is_empty() {
+                words.push(word);
+            }
+        }
+        Opt(ref e) => {
+            *p += 1;
+            if test[*p - 1] == Some(1) {
+                emit(e, opts, test, p, words);
+            } else {
+                *p += param_count(e, opts);
+            }
+        }
+        Repeat(ref rep) => {
+            let (body, min, max) = repeat_bounds(rep, opts);
+            let n = repeat_counts(min, max)[test[*p].unwrap()];
+            *p += 1;
+            let start = *p;
+            for _ in 0..n {
+                *p = start;
+                emit(body, opts, test, p, words);
+            }
+            *p = start + param_count(body, opts);
+        }
+        Select(ref sel) => {
+            let i = test[*p].unwrap();
+            *p += 1;
+            for (j, e) in sel.iter().enumerate() {
+                if j == i {
+                    emit(e, opts, test, p, words);
+                } else {
+                    *p += param_count(e, opts);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use ast::Command;
+    use parser;
+    use matcher;
+    use expand::{self, ExpandOptions};
+
+    fn parse(s: &str) -> Command { parser::parse_command(Tokenizer::new(s.chars())).unwrap().normalize() }
+
+    fn cover(synopsis: &str, strength: usize) -> Vec<Vec<String>> {
+        let cmd = parse(synopsis);
+        let lines = super::cover(&cmd.args, strength, &ExpandOptions::default()).iter()
+            .map(|args| args.iter().map(|w| w.iter().map(|t| t.pretty()).collect()).collect())
+            .collect::<Vec<Vec<String>>>();
+        for args in &lines {
+            assert!(matcher::match_args(&cmd.args, args).is_some(), "{:?}", args);
+        }
+        lines
+    }
+
+    fn has_pair(lines: &[Vec<String>], a: &str, b: &str) -> bool {
+        lines.iter().any(|args| args.iter().any(|w| w == a) && args.iter().any(|w| w == b))
+    }
+
+    #[test]
+    fn pairwise() {
+        let opts = ["-a", "-b", "-c", "-d", "-e", "-f"];
+        let lines = cover("cmd [-a] [-b] [-c] [-d] [-e] [-f]", 2);
+        assert!(lines.len() < 10, "{:?}", lines);
+        for a in &opts {
+            for b in &opts {
+                assert!(has_pair(&lines, a, b), "{} {}", a, b);
+            }
+        }
+        // Both absent, too.
+        assert!(lines.iter().any(|args| !args.contains(&"-a".to_string()) && !args.contains(&"-f".to_string())));
+
+        let lines = cover("cmd {x|y|z} [-o FILE] {-q|-v}", 2);
+        for a in &["x", "y", "z"] {
+            for b in &["-q", "-v", "-o"] {
+                assert!(has_pair(&lines, a, b), "{} {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn nested() {
+        // Choices inside a branch are only combined with that branch taken.
+        let lines = cover("git {add [-n] [-v] | rm [-r] [-f]}", 2);
+        assert!(has_pair(&lines, "-n", "-v"));
+        assert!(has_pair(&lines, "-r", "-f"));
+        assert!(!has_pair(&lines, "-n", "-r"));
+        assert!(cover("cmd {a [b] | c}", 2).contains(&vec!["c".to_string()]));
+        assert_eq!(3, cover("cmd FILE...", 2).len());
+        assert_eq!(vec![vec!["FILE".to_string()]], cover("cmd FILE", 2));
+    }
+
+    #[test]
+    fn exhaustive() {
+        // Covering all the choices gives the full expansion.
+        for s in &["cmd [-a] [-b] {x|y} [--color[=WHEN]]", "cmd {a [b]|c} [d]..."] {
+            let cmd = parse(s);
+            let n = expand::count(&cmd.args, &ExpandOptions::default()).unwrap() as usize;
+            let mut lines = cover(s, 10);
+            assert_eq!(n, lines.len());
+            lines.sort();
+            lines.dedup();
+            assert_eq!(n, lines.len());
+        }
+    }
+
+    #[test]
+    fn repeat_bounds() {
+        let expr = parse("cmd [-v] FILE...").args;
+        // Only the boundary counts are covered, not all the counts up to
+        // `repeat_max`.
+        let opts = ExpandOptions { repeat_max: 1000, ..ExpandOptions::default() };
+        let lines = super::cover(&expr, 2, &opts);
+        let mut counts = lines.iter()
+            .map(|args| args.iter().filter(|w| w[0].pretty() == "FILE").count())
+            .collect::<Vec<_>>();
+        counts.sort();
+        counts.dedup();
+        assert_eq!(vec![1, 2, 1000], counts);
+        assert!(lines.len() <= 6);
+
+        let opts = ExpandOptions { repeat_min: 3, repeat_max: 2, ..ExpandOptions::default() };
+        assert!(super::cover(&expr, 2, &opts).is_empty());
+        let expr = parse("cmd [FILE...]").args;
+        assert!(super::cover(&expr, 2, &opts).is_empty());
+    }
+}
@@ -0,0 +1,582 @@
+//! Enumeration of the command lines a synopsis accepts.
+
+use std::error::Error;
+use std::fmt;
+use token::Token;
+use token::Token::Equals;
+use ast::{Expr, ArgStyle};
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+
+/// A command line argument, made of tokens written without spaces between them.
+pub type Word = Vec<Token>;
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ExpandOptions {
+    /// The maximum number of expansions to yield.
+    pub limit: Option<u64>,
+    /// The maximum number of expansions allowed.  Unlike `limit`, exceeding
+    /// it is an error.
+    pub budget: Option<u64>,
+    /// The range of times `X...` is repeated.  `[X]...` is repeated one time
+    /// less, so it also expands to nothing when `repeat_min` is 1.
+    pub repeat_min: usize,
+    pub repeat_max: usize
+}
+
+impl Default for ExpandOptions {
+    fn default() -> ExpandOptions {
+        ExpandOptions { limit: None, budget: None, repeat_min: 1, repeat_max: 3 }
+    }
+}
+
+impl ExpandOptions {
+    /// Returns options which expand each repeat to its minimal form only.
+    pub fn minimal() -> ExpandOptions {
+        ExpandOptions { repeat_max: 1, ..ExpandOptions::default() }
+    }
+}
+
+// The range of times a repeated expression is repeated.
+#[derive(Clone, Copy)]
+struct Bounds {
+    min: usize,
+    max: usize
+}
+
+/// The error returned when an expansion would exceed the budget.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ExpandError {
+    /// The number of expansions, or `None` if it does not fit in `u64`.
+    pub count: Option<u64>,
+    pub budget: u64
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.count {
+            Some(count) => write!(f, "synopsis expands to {} command lines", count)?,
+            None => write!(f, "synopsis expands to more than {} command lines", u64::MAX)?
+        }
+        write!(f, ", exceeding the budget of {}", self.budget)
+    }
+}
+
+impl Error for ExpandError {}
+
+/// What a placeholder stands for, guessed from its name.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum PlaceholderKind {
+    File,
+    Dir,
+    Number,
+    Other
+}
+
+/// Classifies a placeholder by the words of its name, so that `OUTPUT_FILE`
+/// and `file-name` are files and `DIR` or `N` are a directory and a number.
+pub fn classify(name: &str) -> PlaceholderKind {
+    let words = name.split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.to_uppercase())
+        .collect::<Vec<_>>();
+    let any = |names: &[&str]| words.iter().any(|w| names.contains(&&w[..]));
+    if any(&["DIR", "DIRECTORY", "FOLDER"]) {
+        PlaceholderKind::Dir
+    } else if any(&["FILE", "FILES", "FILENAME", "PATH", "SRC", "SOURCE", "DEST", "TARGET", "SCRIPT", "DEVICE"]) {
+        PlaceholderKind::File
+    } else if any(&["N", "NUM", "NUMBER", "COUNT", "SIZE", "LINES", "BYTES", "SECONDS", "PORT", "LEVEL",
+                    "DEPTH", "WIDTH", "PID"]) {
+        PlaceholderKind::Number
+    } else {
+        PlaceholderKind::Other
+    }
+}
+
+/// Returns the number of expansions of `expr`, or `None` on overflow.
+pub fn count(expr: &Expr, opts: &ExpandOptions) -> Option<u64> {
+    count_bounded(expr, Bounds { min: opts.repeat_min, max: opts.repeat_max })
+}
+
+fn count_bounded(expr: &Expr, bounds: Bounds) -> Option<u64> {
+    let count = |e| count_bounded(e, bounds);
+    match expr.node {
+        Tok(_) => Some(1),
+        OptArg(_, _, OptionalAttached) => Some(2),
+        OptArg(..) => Some(1),
+        Seq(ref seq) | Concat(ref seq) => {
+            seq.iter().try_fold(1u64, |n, e| count(e).and_then(|m| n.checked_mul(m)))
+        }
+        Opt(ref opt) => count(opt).and_then(|n| n.checked_add(1)),
+        Repeat(ref rep) => {
+            let (body, min, max) = repeat_bounds(rep, bounds);
+            power_sum(count(body)?, min, max)
+        }
+        Select(ref sel) => sel.iter().try_fold(0u64, |n, e| count(e).and_then(|m| n.checked_add(m))),
+        Interleave(ref xs) => {
+            // `by_len[k]` is the number of expansions of the elements in which
+            // `k` elements expand to some words, which can be ordered in `k!`
+            // ways.
+            let mut by_len = vec![1u64];
+            for x in xs {
+                let n = count(x)?;
+                let empty = count_empty(x, bounds)?;
+                let mut next = vec![0u64; by_len.len() + 1];
+                for (k, &m) in by_len.iter().enumerate() {
+                    next[k] = next[k].checked_add(m.checked_mul(empty)?)?;
+                    next[k + 1] = next[k + 1].checked_add(m.checked_mul(n - empty)?)?;
+                }
+                by_len = next;
+            }
+            by_len.iter().enumerate()
+                .filter(|&(_, &m)| m > 0)
+                .try_fold(0u64, |sum, (k, &m)| factorial(k)?.checked_mul(m)?.checked_add(sum))
+        }
+    }
+}
+
+// Returns the number of expansions of `expr` which have no words.
+fn count_empty(expr: &Expr, bounds: Bounds) -> Option<u64> {
+    let count = |e| count_empty(e, bounds);
+    match expr.node {
+        Tok(_) | OptArg(..) => Some(0),
+        Seq(ref seq) | Concat(ref seq) | Interleave(ref seq) => {
+            seq.iter().try_fold(1u64, |n, e| count(e).and_then(|m| n.checked_mul(m)))
+        }
+        Opt(ref opt) => count(opt).and_then(|n| n.checked_add(1)),
+        Repeat(ref rep) => {
+            let (body, min, max) = repeat_bounds(rep, bounds);
+            power_sum(count(body)?, min, max)
+        }
+        Select(ref sel) => sel.iter().try_fold(0u64, |n, e| count(e).and_then(|m| n.checked_add(m)))
+    }
+}
+
+// Returns the sum of `n.pow(k)` for `k` in `min..=max`.
+fn power_sum(n: u64, min: usize, max: usize) -> Option<u64> {
+    if min > max {
+        return Some(0)
+    }
+    match n {
+        0 => Some(if min == 0 { 1 } else { 0 }),
+        1 => (max - min).checked_add(1).map(|n| n as u64),
+        // Terminates soon, since `n.pow(k)` overflows for k >= 64.
+        n => (min..=max).try_fold(0u64, |sum, k| {
+            if k > u32::MAX as usize { return None }
+            n.checked_pow(k as u32).and_then(|m| sum.checked_add(m))
+        })
+    }
+}
+
+fn factorial(n: usize) -> Option<u64> { (1..n as u64 + 1).try_fold(1u64, |f, k| f.checked_mul(k)) }
+
+/// Returns an iterator over the expansions of `expr`, each of which is a list
+/// of words.
+///
+/// The number of expansions is checked against `opts.budget` before anything
+/// is enumerated.
+pub fn expand<'a>(expr: &'a Expr, opts: &ExpandOptions) -> Result<Expansions<'a>, ExpandError> {
+    let bounds = Bounds { min: opts.repeat_min, max: opts.repeat_max };
+    if let Some(budget) = opts.budget {
+        let count = count_bounded(expr, bounds);
+        let yielded = match (count, opts.limit) {
+            (Some(n), Some(limit)) => Some(n.min(limit)),
+            (None, limit) => limit,
+            (n, None) => n
+        };
+        if yielded.is_none_or(|n| n > budget) {
+            return Err(ExpandError { count, budget })
+        }
+    }
+    Ok(Expansions { state: State::first(expr, bounds), bounds, remaining: opts.limit })
+}
+
+/// A lazy iterator over the expansions of an expression.
+pub struct Expansions<'a> {
+    state: Option<State<'a>>,
+    bounds: Bounds,
+    remaining: Option<u64>
+}
+
+impl<'a> Iterator for Expansions<'a> {
+    type Item = Vec<Word>;
+
+    fn next(&mut self) -> Option<Vec<Word>> {
+        if self.remaining == Some(0) {
+            return None
+        }
+        let words = {
+            let state = self.state.as_mut()?;
+            let mut words = vec![];
+            state.emit(&mut words);
+            if !state.advance(self.bounds) {
+                self.state = None;
+            }
+            words
+        };
+        self.remaining = self.remaining.map(|n| n - 1);
+        Some(words)
+    }
+}
+
+// Returns the body of a `Repeat` and how many times it is repeated.
+fn repeat_bounds(body: &Expr, bounds: Bounds) -> (&Expr, usize, usize) {
+    match body.node {
+        Opt(ref body) => (body, bounds.min.saturating_sub(1), bounds.max.saturating_sub(1)),
+        _ => (body, bounds.min, bounds.max)
+    }
+}
+
+// The current expansion of an expression.  Each state yields its expansions
+// in turn, like the digits of an odometer.
+enum State<'a> {
+    Tok(&'a Token),
+    // `true` if the attached form is chosen.
+    OptArg(&'a Token, &'a Token, ArgStyle, bool),
+    Seq(Vec<(&'a Expr, State<'a>)>),
+    Concat(Vec<(&'a Expr, State<'a>)>),
+    Opt(&'a Expr, Option<Box<State<'a>>>),
+    Repeat(&'a Expr, usize, Vec<(&'a Expr, State<'a>)>),
+    Select(&'a [Expr], usize, Box<State<'a>>),
+    // The indices of the elements which expand to some words, in the order
+    // they are written.
+    Interleave(Vec<(&'a Expr, State<'a>)>, Vec<usize>)
+}
+
+impl<'a> State<'a> {
+    // Returns the state of the first expansion of `expr`, or `None` if `expr`
+    // has no expansions.
+    fn first(expr: &'a Expr, bounds: Bounds) -> Option<State<'a>> {
+        let state = match expr.node {
+            Tok(ref tok) => State::Tok(tok),
+            OptArg(ref opt, ref arg, style) => State::OptArg(opt, arg, style, style == Attached),
+            Seq(ref seq) => State::Seq(first_seq(seq.iter(), bounds)?),
+            Concat(ref seq) => State::Concat(first_seq(seq.iter(), bounds)?),
+            Interleave(ref xs) => {
+                let elems = first_seq(xs.iter(), bounds)?;
+                let order = non_empty(&elems);
+                State::Interleave(elems, order)
+            }
+            Opt(ref opt) => State::Opt(opt, None),
+            Repeat(ref rep) => {
+                // If `min` copies of the body have no expansion, neither do
+                // more copies.
+                let (body, min, max) = repeat_bounds(rep, bounds);
+                if min > max {
+                    return None
+                }
+                return first_seq((0..min).map(|_| body), bounds).map(|seq| State::Repeat(rep, min, seq))
+            }
+            Select(ref sel) => {
+                return sel.iter().enumerate()
+                    .filter_map(|(i, e)| State::first(e, bounds).map(|s| State::Select(sel, i, Box::new(s))))
+                    .next()
+            }
+        };
+        Some(state)
+    }
+
+    // Moves to the next expansion.  Returns `false` if there is none.
+    fn advance(&mut self, bounds: Bounds) -> bool {
+        match *self {
+            State::Tok(_) => false,
+            State::OptArg(_, _, OptionalAttached, ref mut attached) if !*attached => {
+                *attached = true;
+                true
+            }
+            State::OptArg(..) => false,
+            State::Seq(ref mut seq) | State::Concat(ref mut seq) => advance_seq(seq, bounds),
+            State::Interleave(ref mut elems, ref mut order) => {
+                if next_permutation(order) {
+                    return true
+                }
+                if !advance_seq(elems, bounds) {
+                    return false
+                }
+                *order = non_empty(elems);
+                true
+            }
+            State::Opt(opt, ref mut state) => {
+                match *state {
+                    Some(ref mut s) => s.advance(bounds),
+                    None => {
+                        *state = State::first(opt, bounds).map(Box::new);
+                        state.is_some()
+                    }
+                }
+            }
+            State::Repeat(rep, ref mut n, ref mut seq) => {
+                if advance_seq(seq, bounds) {
+                    return true
+                }
+                let (body, _, max) = repeat_bounds(rep, bounds);
+                if *n >= max {
+                    return false
+                }
+                *n += 1;
+                match first_seq((0..*n).map(|_| body), bounds) {
+                    Some(s) => { *seq = s; true }
+                    None => false
+                }
+            }
+            State::Select(sel, ref mut i, ref mut state) => {
+                if state.advance(bounds) {
+                    return true
+                }
+                while *i + 1 < sel.len() {
+                    *i += 1;
+                    if let Some(s) = State::first(&sel[*i], bounds) {
+                        **state = s;
+                        return true
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    fn emit(&self, words: &mut Vec<Word>) {
+        match *self {
+            State::Tok(tok) => words.push(vec![tok.clone()]),
+            State::OptArg(opt, arg, style, attached) => {
+                if attached {
+                    words.push(vec![opt.clone(), Equals, arg.clone()]);
+                } else if style == Separate {
+                    words.push(vec![opt.clone()]);
+                    words.push(vec![arg.clone()]);
+                } else {
+                    words.push(vec![opt.clone()]);
+                }
+            }
+            State::Seq(ref seq) | State::Repeat(_, _, ref seq) => {
+                for (_, s) in seq {
+                    s.emit(words);
+                }
+            }
+            State::Concat(ref seq) => {
+                let mut parts = vec![];
+                for (_, s) in seq {
+                    s.emit(&mut parts);
+                }
+                let word = parts.into_iter().flatten().collect::<Word>();
+                if !word.is_empty() {
+                    words.push(word);
+                }
+            }
+            State::Opt(_, ref state) => {
+                if let Some(ref s) = *state {
+                    s.emit(words);
+                }
+            }
+            State::Select(_, _, ref state) => state.emit(words),
+            State::Interleave(ref elems, ref order) => {
+                for &i in order {
+                    elems[i].1.emit(words);
+                }
+            }
+        }
+    }
+}
+
+fn non_empty(elems: &[(&Expr, State)]) -> Vec<usize> {
+    elems.iter().enumerate()
+        .filter(|(_, (_, s))| {
+            let mut words = vec![];
+            s.emit(&mut words);
+            !words.is_empty()
+        }).map(|(i, _)| i)
+        .collect()
+}
+
+// Rearranges `v` into the next permutation in lexicographic order.  Returns
+// `false` if `v` is the last one.
+fn next_permutation(v: &mut [usize]) -> bool {
+    let i = match (1..v.len()).rev().find(|&i| v[i - 1] < v[i]) {
+        Some(i) => i,
+        None => return false
+    };
+    let j = (i..v.len()).rev().find(|&j| v[i - 1] < v[j]).unwrap();
+    v.swap(i - 1, j);
+    v[i..].reverse();
+    true
+}
+
+fn first_seq<'a, I>(seq: I, bounds: Bounds) -> Option<Vec<(&'a Expr, State<'a>)>>
+    where I: Iterator<Item = &'a Expr>
+{
+    seq.map(|e| State::first(e, bounds).map(|s| (e, s))).collect()
+}
+
+// Advances a sequence, the first element changing fastest.
+fn advance_seq<'a>(seq: &mut [(&'a Expr, State<'a>)], bounds: Bounds) -> bool {
+    for &mut (expr, ref mut state) in seq.iter_mut() {
+        if state.advance(bounds) {
+            return true
+        }
+        // Every element had a first expansion when the sequence was built.
+        *state = State::first(expr, bounds).unwrap();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Expr;
+    use token::Token::{Text, Placeholder, ShortOpt, LongOpt, Equals};
+    use ast::ExprKind::{Tok, OptArg, Seq, Concat, Opt, Repeat, Select, Interleave};
+    use ast::ArgStyle::{Separate, Attached, OptionalAttached};
+    use super::{ExpandOptions, ExpandError, Word};
+
+    fn expand(e: &Expr) -> Vec<Vec<Word>> {
+        expand_with(e, &ExpandOptions::default())
+    }
+
+    fn expand_with(e: &Expr, opts: &ExpandOptions) -> Vec<Vec<Word>> {
+        let v = super::expand(e, opts).unwrap().collect::<Vec<_>>();
+        assert_eq!(Some(v.len() as u64), super::count(e, opts));
+        v
+    }
+
+    fn text_tok(v: Vec<Vec<&str>>) -> Vec<Vec<Word>> {
+        v.into_iter()
+            .map(|x| x.into_iter().map(|s| vec![Text(s.to_string())]).collect())
+            .collect()
+    }
+    fn text(s: &str) -> Expr { Tok(Text(s.to_string())).into() }
+    fn seq(v: Vec<Expr>) -> Expr { Seq(v).into() }
+    fn opt(e: Expr) -> Expr { Opt(Box::new(e)).into() }
+    fn rep(e: Expr) -> Expr { Repeat(Box::new(e)).into() }
+    fn sel(v: Vec<Expr>) -> Expr { Select(v).into() }
+    fn cat(v: Vec<Expr>) -> Expr { Concat(v).into() }
+
+    #[test]
+    fn expand_placeholder() {
+        let ph = Tok(Placeholder("FILE".to_string())).into();
+        assert_eq!(vec![vec![vec![Text("rm".to_string())]],
+                        vec![vec![Text("rm".to_string())], vec![Placeholder("FILE".to_string())]]],
+                   expand(&seq(vec![text("rm"), opt(ph)])));
+    }
+
+    #[test]
+    fn expand_option_arg() {
+        let o = ShortOpt("o".to_string());
+        let color = LongOpt("color".to_string());
+        let file = Placeholder("FILE".to_string());
+        assert_eq!(vec![vec![vec![o.clone()], vec![file.clone()]]],
+                   expand(&OptArg(o.clone(), file.clone(), Separate).into()));
+        assert_eq!(vec![vec![vec![o.clone(), Equals, file.clone()]]],
+                   expand(&OptArg(o.clone(), file.clone(), Attached).into()));
+        assert_eq!(vec![vec![vec![color.clone()]], vec![vec![color.clone(), Equals, file.clone()]]],
+                   expand(&OptArg(color.clone(), file.clone(), OptionalAttached).into()));
+    }
+
+    #[test]
+    fn expand_concat() {
+        let x = ShortOpt("x".to_string());
+        let y = Text("y".to_string());
+        assert_eq!(vec![vec![vec![x.clone()]], vec![vec![x.clone(), y.clone()]]],
+                   expand(&cat(vec![Tok(x.clone()).into(), opt(text("y"))])));
+        assert_eq!(vec![vec![], vec![vec![y.clone()]]],
+                   expand(&cat(vec![opt(text("y"))])));
+        assert_eq!(text_tok(vec![vec!["a", "c"], vec!["b", "c"]]).into_iter()
+                   .map(|words| vec![words.into_iter().flatten().collect()])
+                   .collect::<Vec<Vec<Word>>>(),
+                   expand(&cat(vec![sel(vec![text("a"), text("b")]), text("c")])));
+    }
+
+    #[test]
+    fn expand_all() {
+        assert_eq!(text_tok(vec![vec!["a"]]), expand(&text("a")));
+        assert_eq!(text_tok(vec![vec!["a", "b"]]),
+                   expand(&seq(vec![text("a"), text("b")])));
+        assert_eq!(text_tok(vec![vec![], vec!["a"]]),
+                   expand(&opt(text("a"))));
+        assert_eq!(text_tok(vec![vec!["a"], vec!["a", "a"], vec!["a", "a", "a"]]),
+                   expand(&rep(text("a"))));
+        assert_eq!(text_tok(vec![vec![], vec!["a"], vec!["a", "a"]]),
+                   expand(&rep(opt(text("a")))));
+        assert_eq!(text_tok(vec![vec!["a"], vec!["b"], vec!["c"]]),
+                   expand(&sel(vec![text("a"), text("b"), text("c")])));
+
+        assert_eq!(text_tok(vec![vec!["a", "c"], vec!["b", "c"]]),
+                   expand(&seq(vec![sel(vec![text("a"), text("b")]), text("c")])));
+        assert_eq!(text_tok(vec![vec!["a", "c"], vec!["b", "c"], vec!["a", "d"], vec!["b", "d"]]),
+                   expand(&seq(vec![sel(vec![text("a"), text("b")]),
+                                    sel(vec![text("c"), text("d")])])));
+        assert_eq!(text_tok(vec![vec!["a"], vec!["b"], vec!["a", "a"], vec!["b", "a"],
+                                 vec!["a", "b"], vec!["b", "b"]]),
+                   expand(&rep(opt(sel(vec![text("a"), text("b")])))).into_iter().skip(1).collect::<Vec<_>>());
+        assert!(expand(&sel(vec![])).is_empty());
+        assert_eq!(text_tok(vec![vec![]]), expand(&opt(sel(vec![]))));
+    }
+
+    #[test]
+    fn limit_and_budget() {
+        let e = seq((0..70).map(|i| opt(text(&i.to_string()))).collect());
+        assert_eq!(None, super::count(&e, &ExpandOptions::default()));
+        let opts = ExpandOptions { limit: Some(3), ..ExpandOptions::default() };
+        assert_eq!(3, super::expand(&e, &opts).unwrap().count());
+
+        let opts = ExpandOptions { budget: Some(1000), ..ExpandOptions::default() };
+        assert_eq!(ExpandError { count: None, budget: 1000 },
+                   super::expand(&e, &opts).err().unwrap());
+        let e = seq((0..10).map(|i| opt(text(&i.to_string()))).collect());
+        assert_eq!(ExpandError { count: Some(1024), budget: 1000 },
+                   super::expand(&e, &opts).err().unwrap());
+        let opts = ExpandOptions { limit: Some(1000), budget: Some(1000), ..ExpandOptions::default() };
+        assert_eq!(1000, super::expand(&e, &opts).unwrap().count());
+    }
+
+    #[test]
+    fn repeat_bounds() {
+        let opts = ExpandOptions { repeat_min: 2, repeat_max: 3, ..ExpandOptions::default() };
+        assert_eq!(text_tok(vec![vec!["a", "a"], vec!["a", "a", "a"]]),
+                   expand_with(&rep(text("a")), &opts));
+        assert_eq!(text_tok(vec![vec!["a"], vec!["a", "a"]]),
+                   expand_with(&rep(opt(text("a"))), &opts));
+
+        let opts = ExpandOptions::minimal();
+        assert_eq!(text_tok(vec![vec!["a"]]), expand_with(&rep(text("a")), &opts));
+        assert_eq!(text_tok(vec![vec![]]), expand_with(&rep(opt(text("a"))), &opts));
+        assert_eq!(text_tok(vec![vec!["a"], vec!["b"]]),
+                   expand_with(&rep(sel(vec![text("a"), text("b")])), &opts));
+
+        let opts = ExpandOptions { repeat_min: 0, repeat_max: 1, ..ExpandOptions::default() };
+        assert_eq!(text_tok(vec![vec![], vec!["a"]]), expand_with(&rep(text("a")), &opts));
+
+        let opts = ExpandOptions { repeat_min: 3, repeat_max: 2, ..ExpandOptions::default() };
+        assert!(expand_with(&rep(text("a")), &opts).is_empty());
+
+        let opts = ExpandOptions { repeat_min: 0, repeat_max: usize::MAX, ..ExpandOptions::default() };
+        assert_eq!(None, super::count(&rep(text("a")), &opts));
+        assert_eq!(None, super::count(&rep(sel(vec![text("a"), text("b")])), &opts));
+        assert_eq!(text_tok(vec![vec![], vec!["a"], vec!["a", "a"]]),
+                   super::expand(&rep(text("a")), &opts).unwrap().take(3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn classify() {
+        use super::PlaceholderKind::*;
+        assert_eq!(File, super::classify("FILE"));
+        assert_eq!(File, super::classify("output-file"));
+        assert_eq!(Dir, super::classify("DIR"));
+        assert_eq!(Dir, super::classify("TARGET_DIR"));
+        assert_eq!(Number, super::classify("N"));
+        assert_eq!(Other, super::classify("PATTERN"));
+        assert_eq!(Other, super::classify("PROFILE"));
+    }
+
+    #[test]
+    fn interleave() {
+        let il = |v| Interleave(v).into();
+        assert_eq!(text_tok(vec![vec!["a", "b"], vec!["b", "a"]]),
+                   expand(&il(vec![text("a"), text("b")])));
+        assert_eq!(text_tok(vec![vec![], vec!["a"], vec!["b"], vec!["a", "b"], vec!["b", "a"]]),
+                   expand(&il(vec![opt(text("a")), opt(text("b"))])));
+        assert_eq!(text_tok(vec![vec!["a", "b", "c"], vec!["a", "c", "b"], vec!["b", "a", "c"],
+                                 vec!["b", "c", "a"], vec!["c", "a", "b"], vec!["c", "b", "a"]]),
+                   expand(&il(vec![text("a"), text("b"), text("c")])));
+        expand(&il(vec![opt(text("a")), rep(opt(text("b"))), sel(vec![text("c"), opt(text("d"))])]));
+        let e = il((0..30).map(|i| text(&i.to_string())).collect());
+        assert_eq!(None, super::count(&e, &ExpandOptions::default()));
+    }
+}
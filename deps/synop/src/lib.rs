@@ -2,14 +2,34 @@
 #![crate_type = "lib"]
 #![warn(unused, bad_style)]
 
-#![feature(io)]
-
 pub use token::{Token, Tokenizer};
-pub use ast::Expr;
-pub use parser::{ParseResult, parse};
-pub use util::{ReadResult, read_ast};
+pub use span::{Pos, Span, Spanned};
+pub use ast::{Expr, ExprKind, ArgStyle, Command};
+pub use parser::{ParseResult, ParseError, ParseErrorKind, parse, parse_command};
+pub use synopsis::{Synopsis, FormSeparator};
+pub use expand::{Word, ExpandOptions, ExpandError, Expansions, PlaceholderKind, expand, classify};
+pub use matcher::{Derivation, match_args, match_command};
+pub use bindings::{Value, Bindings, bind};
+pub use automaton::{Dfa, CompileError, compile};
+pub use random::{Rng, RandomOptions, sample};
+pub use cover::cover;
+pub use mutate::{Mutation, Mutant, mutants};
+pub use complete::Shell;
+pub use util::{ReadResult, ReadError, read_ast, read_synopses};
 
 pub mod token;
+pub mod span;
 pub mod ast;
+pub mod synopsis;
+pub mod expand;
+pub mod matcher;
+pub mod bindings;
+pub mod automaton;
+pub mod random;
+pub mod cover;
+pub mod mutate;
+pub mod complete;
+pub mod roff;
+pub mod mdoc;
 mod parser;
 mod util;
@@ -0,0 +1,366 @@
+//! Matching of command lines against synopses.
+//!
+//! A token matches a whole argument; `Concat` elements match parts of one
+//! argument.  A placeholder matches any argument not looking like an option.
+
+use std::path::Path;
+use token::Token;
+use token::Token::Placeholder;
+use ast::{Expr, Command};
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+
+/// How a command line was derived from an expression.  Its structure follows
+/// that of the expression it was matched against.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Derivation {
+    /// The text the token matched.
+    Tok(String),
+    /// The argument of the option, if one was given.
+    OptArg(Option<String>),
+    Seq(Vec<Derivation>),
+    Concat(Vec<Derivation>),
+    /// The derivation of the optional expression, if it was taken.
+    Opt(Option<Box<Derivation>>),
+    /// The derivation of each iteration.
+    Repeat(Vec<Derivation>),
+    /// The index of the branch taken, and its derivation.
+    Select(usize, Box<Derivation>),
+    /// The index and the derivation of each element, in the order they were
+    /// matched.
+    Interleave(Vec<(usize, Derivation)>)
+}
+
+/// Matches `args` against `expr`.  Returns the derivation of the match, if
+/// any.
+pub fn match_args(expr: &Expr, args: &[String]) -> Option<Derivation> {
+    match_words(expr, args, 0).into_iter()
+        .find(|&(end, _)| end == args.len())
+        .map(|(_, d)| d)
+}
+
+/// Matches `argv`, which starts with the program name, against `cmd`.  The
+/// program may be given by a path, as `$0` of a script run as `./script`.
+pub fn match_command(cmd: &Command, argv: &[String]) -> Option<Derivation> {
+    match argv.split_first() {
+        Some((arg0, args)) if is_program(&cmd.name, arg0) => match_args(&cmd.args, args),
+        _ => None
+    }
+}
+
+/// Returns true if `arg0`, the first word of a command line, runs the program
+/// `name`, either by the name or by a path ending with it.
+pub fn is_program(name: &str, arg0: &str) -> bool {
+    arg0 == name || Path::new(arg0).file_name().is_some_and(|base| base == name)
+}
+
+/// Returns true if `word` can be the value of a placeholder, that is, it is
+/// not empty and does not look like an option.
+pub fn is_operand(word: &str) -> bool {
+    !word.is_empty() && (word == "-" || !word.starts_with('-'))
+}
+
+// The positions where a match can end, each with its first derivation found.
+type Matches = Vec<(usize, Derivation)>;
+
+// A position in an `Interleave`, the elements matched so far, and their
+// derivations.
+type InterleaveState = (usize, Vec<bool>, Vec<(usize, Derivation)>);
+
+fn push_match<T>(v: &mut Vec<(usize, T)>, end: usize, d: T) {
+    if !v.iter().any(|&(e, _)| e == end) {
+        v.push((end, d));
+    }
+}
+
+// Matches `expr` against the words of `args` starting at `i`.
+fn match_words(expr: &Expr, args: &[String], i: usize) -> Matches {
+    let word = args.get(i).map(|s| &s[..]);
+    match expr.node {
+        Tok(ref tok) => {
+            match word {
+                Some(w) if matches_word(tok, w) => vec![(i + 1, Derivation::Tok(w.to_string()))],
+                _ => vec![]
+            }
+        }
+        OptArg(ref opt, ref arg, style) => {
+            let w = match word {
+                Some(w) => w,
+                None => return vec![]
+            };
+            let opt = opt.pretty();
+            if style == Separate {
+                return match args.get(i + 1) {
+                    Some(a) if w == opt && matches_word(arg, a) => {
+                        vec![(i + 2, Derivation::OptArg(Some(a.to_string())))]
+                    }
+                    _ => vec![]
+                }
+            }
+            if style == OptionalAttached && w == opt {
+                return vec![(i + 1, Derivation::OptArg(None))]
+            }
+            match w.strip_prefix(&opt[..]).and_then(|rest| rest.strip_prefix('=')) {
+                Some(value) if matches_word(arg, value) => {
+                    vec![(i + 1, Derivation::OptArg(Some(value.to_string())))]
+                }
+                _ => vec![]
+            }
+        }
+        Concat(ref seq) => {
+            let mut v = vec![];
+            // Elements which all expand to nothing do not form a word.
+            if !match_seq(seq, 0, &|e, off| match_chars(e, "", off)).is_empty() {
+                push_match(&mut v, i, Derivation::Concat(vec![]));
+            }
+            if let Some(w) = word {
+                let ends = match_seq(seq, 0, &|e, off| match_chars(e, w, off));
+                if let Some((_, ds)) = ends.into_iter().find(|&(end, _)| end == w.len()) {
+                    push_match(&mut v, i + 1, Derivation::Concat(ds));
+                }
+            }
+            v
+        }
+        Seq(_) | Opt(_) | Repeat(_) | Select(_) | Interleave(_) => {
+            match_nested(expr, i, &|e, j| match_words(e, args, j))
+        }
+    }
+}
+
+// Matches `expr` against `word` starting at the byte offset `off`.
+fn match_chars(expr: &Expr, word: &str, off: usize) -> Matches {
+    match expr.node {
+        Tok(ref tok) => {
+            prefix_ends(tok, word, off).into_iter()
+                .map(|end| (end, Derivation::Tok(word[off..end].to_string())))
+                .collect()
+        }
+        OptArg(ref opt, ref arg, style) => {
+            let mut v = vec![];
+            if style == Separate {
+                return v
+            }
+            let opt = opt.pretty();
+            if !word[off..].starts_with(&opt[..]) {
+                return v
+            }
+            let start = off + opt.len();
+            if word[start..].starts_with('=') {
+                for end in prefix_ends(arg, word, start + 1) {
+                    push_match(&mut v, end, Derivation::OptArg(Some(word[start + 1..end].to_string())));
+                }
+            }
+            if style == OptionalAttached {
+                push_match(&mut v, start, Derivation::OptArg(None));
+            }
+            v
+        }
+        Seq(_) | Concat(_) | Opt(_) | Repeat(_) | Select(_) | Interleave(_) => {
+            match_nested(expr, off, &|e, p| match_chars(e, word, p))
+        }
+    }
+}
+
+// Matches an expression made of other expressions, which are matched by `f`.
+fn match_nested<F>(expr: &Expr, start: usize, f: &F) -> Matches
+    where F: Fn(&Expr, usize) -> Matches
+{
+    match expr.node {
+        Seq(ref seq) => {
+            match_seq(seq, start, f).into_iter().map(|(end, ds)| (end, Derivation::Seq(ds))).collect()
+        }
+        Concat(ref seq) => {
+            match_seq(seq, start, f).into_iter().map(|(end, ds)| (end, Derivation::Concat(ds))).collect()
+        }
+        Opt(ref e) => {
+            let mut v = f(e, start).into_iter()
+                .map(|(end, d)| (end, Derivation::Opt(Some(Box::new(d)))))
+                .collect::<Vec<_>>();
+            push_match(&mut v, start, Derivation::Opt(None));
+            v
+        }
+        Repeat(ref e) => {
+            let mut v = vec![];
+            let mut frontier = vec![(start, vec![])];
+            while !frontier.is_empty() {
+                let mut next = vec![];
+                for (pos, ds) in frontier {
+                    for (end, d) in f(e, pos) {
+                        // Iterations after the first must consume something.
+                        if end == pos && !ds.is_empty() || v.iter().any(|&(e, _)| e == end) {
+                            continue
+                        }
+                        let mut ds = ds.clone();
+                        ds.push(d);
+                        v.push((end, Derivation::Repeat(ds.clone())));
+                        next.push((end, ds));
+                    }
+                }
+                frontier = next;
+            }
+            v
+        }
+        Select(ref sel) => {
+            let mut v = vec![];
+            for (i, e) in sel.iter().enumerate() {
+                for (end, d) in f(e, start) {
+                    push_match(&mut v, end, Derivation::Select(i, Box::new(d)));
+                }
+            }
+            v
+        }
+        Interleave(ref xs) => {
+            // Matches one more element at each step.  States are the position,
+            // the elements matched so far, and their derivations.
+            let mut states = vec![(start, vec![false; xs.len()], vec![])];
+            for _ in xs {
+                let mut next: Vec<InterleaveState> = vec![];
+                for &(pos, ref used, ref ds) in &states {
+                    for (i, e) in xs.iter().enumerate().filter(|&(i, _)| !used[i]) {
+                        for (end, d) in f(e, pos) {
+                            let mut used = used.clone();
+                            used[i] = true;
+                            if next.iter().any(|&(e, ref u, _)| e == end && *u == used) {
+                                continue
+                            }
+                            let mut ds = ds.clone();
+                            ds.push((i, d));
+                            next.push((end, used, ds));
+                        }
+                    }
+                }
+                states = next;
+            }
+            let mut v = vec![];
+            for (end, _, ds) in states {
+                push_match(&mut v, end, Derivation::Interleave(ds));
+            }
+            v
+        }
+        Tok(_) | OptArg(..) => vec![]
+    }
+}
+
+fn match_seq<F>(seq: &[Expr], start: usize, f: &F) -> Vec<(usize, Vec<Derivation>)>
+    where F: Fn(&Expr, usize) -> Matches
+{
+    let mut states = vec![(start, vec![])];
+    for e in seq {
+        let mut next = vec![];
+        for &(pos, ref ds) in &states {
+            for (end, d) in f(e, pos) {
+                let mut ds: Vec<Derivation> = ds.clone();
+                ds.push(d);
+                push_match(&mut next, end, ds);
+            }
+        }
+        states = next;
+    }
+    states
+}
+
+// Returns true if `tok` matches the whole `word`.
+fn matches_word(tok: &Token, word: &str) -> bool {
+    match *tok {
+        Placeholder(_) => is_operand(word),
+        _ => word == tok.pretty()
+    }
+}
+
+// Returns the offsets in `word` where a match of `tok` starting at `off` can end.
+fn prefix_ends(tok: &Token, word: &str, off: usize) -> Vec<usize> {
+    match *tok {
+        Placeholder(_) => {
+            word[off..].char_indices().skip(1).map(|(i, _)| off + i).chain(Some(word.len()))
+                .filter(|&end| end > off)
+                .collect()
+        }
+        _ => {
+            let lit = tok.pretty();
+            if word[off..].starts_with(&lit[..]) { vec![off + lit.len()] } else { vec![] }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use ast::Command;
+    use parser;
+    use super::Derivation;
+    use super::Derivation::*;
+
+    fn parse(s: &str) -> Command { parser::parse_command(Tokenizer::new(s.chars())).unwrap().normalize() }
+
+    fn match_line(synopsis: &str, line: &str) -> Option<Derivation> {
+        let argv = line.split_whitespace().map(|s| s.to_string()).collect::<Vec<_>>();
+        super::match_command(&parse(synopsis), &argv)
+    }
+
+    fn tok(s: &str) -> Derivation { Tok(s.to_string()) }
+
+    #[test]
+    fn program_path() {
+        assert!(match_line("myscript [-v] FILE", "./myscript -v a").is_some());
+        assert!(match_line("myscript [-v] FILE", "/usr/bin/myscript a").is_some());
+        assert!(match_line("myscript [-v] FILE", "./other a").is_none());
+    }
+    fn taken(d: Derivation) -> Derivation { Opt(Some(Box::new(d))) }
+
+    #[test]
+    fn simple() {
+        assert_eq!(Some(Seq(vec![])), match_line("ls", "ls"));
+        assert_eq!(None, match_line("ls", "ls -l"));
+        assert_eq!(None, match_line("ls", "cp"));
+        assert_eq!(Some(Seq(vec![taken(tok("-l")), tok("a")])), match_line("ls [-l] FILE", "ls -l a"));
+        assert_eq!(Some(Seq(vec![Opt(None), tok("a")])), match_line("ls [-l] FILE", "ls a"));
+        assert_eq!(None, match_line("ls [-l] FILE", "ls -x"));
+        assert_eq!(None, match_line("ls [-l] FILE", "ls"));
+    }
+
+    #[test]
+    fn select_and_repeat() {
+        assert_eq!(Some(Seq(vec![Select(1, Box::new(tok("-b"))), Repeat(vec![tok("x"), tok("y")])])),
+                   match_line("cmd {-a|-b} FILE...", "cmd -b x y"));
+        assert_eq!(Some(Repeat(vec![taken(tok("-v")), taken(tok("-v"))])),
+                   match_line("cmd [-v]...", "cmd -v -v"));
+        assert_eq!(Some(Repeat(vec![Opt(None)])), match_line("cmd [-v]...", "cmd"));
+        assert_eq!(None, match_line("cmd FILE...", "cmd"));
+    }
+
+    #[test]
+    fn option_args() {
+        assert_eq!(Some(OptArg(Some("out".to_string()))), match_line("cc {-o FILE}", "cc -o out"));
+        assert_eq!(None, match_line("cc {-o FILE}", "cc -o -x"));
+        assert_eq!(Some(OptArg(Some("a".to_string()))), match_line("cc --out=FILE", "cc --out=a"));
+        assert_eq!(None, match_line("cc --out=FILE", "cc --out="));
+        assert_eq!(Some(OptArg(None)), match_line("ls --color[=WHEN]", "ls --color"));
+        assert_eq!(Some(OptArg(Some("auto".to_string()))),
+                   match_line("ls --color[=WHEN]", "ls --color=auto"));
+    }
+
+    #[test]
+    fn concat() {
+        assert_eq!(Some(Concat(vec![tok("-I"), tok("dir")])), match_line("cc -I{DIR}", "cc -Idir"));
+        assert_eq!(None, match_line("cc -I{DIR}", "cc -I dir"));
+        assert_eq!(Some(Concat(vec![tok("x"), Opt(None), tok(".c")])),
+                   match_line("cc NAME[-opt].c", "cc x.c"));
+        assert_eq!(Some(Concat(vec![tok("x"), taken(tok("-opt")), tok(".c")])),
+                   match_line("cc NAME[-opt].c", "cc x-opt.c"));
+        assert_eq!(Some(Seq(vec![Concat(vec![]), tok("a")])), match_line("cmd [x][y] a", "cmd a"));
+    }
+
+    #[test]
+    fn interleave() {
+        let cmd = parse("ls [-a] [-l] [-o FILE] DIR").infer_unordered();
+        let argv = |line: &str| line.split_whitespace().map(|s| s.to_string()).collect::<Vec<_>>();
+        let arg = |s: &str| OptArg(Some(s.to_string()));
+        assert_eq!(Some(Seq(vec![Interleave(vec![(0, Opt(None)), (2, taken(arg("x"))), (1, taken(tok("-l")))]),
+                                 tok("d")])),
+                   super::match_command(&cmd, &argv("ls -o x -l d")));
+        assert!(super::match_command(&cmd, &argv("ls -l -a d")).is_some());
+        assert!(super::match_command(&cmd, &argv("ls d")).is_some());
+        assert_eq!(None, super::match_command(&cmd, &argv("ls -l -l d")));
+        assert_eq!(None, super::match_command(&parse("ls [-a] [-l] DIR"), &argv("ls -l -a d")));
+    }
+}
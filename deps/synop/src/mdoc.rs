@@ -0,0 +1,497 @@
+//! Generation of mdoc(7) SYNOPSIS sections.
+//!
+//! Each form starts with `.Nm`, followed by a line for each element of the
+//! arguments.  Options are written with `.Fl`, placeholders with `.Ar` and
+//! keywords with `.Ic`.  Optional elements are `.Op` at the start of a line
+//! and `Oo`/`Oc` within one, alternatives are separated by `|` and grouped by
+//! `Bro`/`Brc`, and elements written without spaces are joined by `Ns`.
+//!
+//! `import` reads the SYNOPSIS section of a page back into synopsis forms.
+
+use token::{Token, Tokenizer};
+use token::Token::*;
+use span::{Pos, Span, Spanned};
+use ast::{Command, Expr};
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+use parser::{self, ParseResult};
+
+const DELIMITERS: &[&str] = &[".", ",", ";", ":", "?", "!", "(", ")", "[", "]", "|"];
+
+// Escapes `s` as a macro argument, so that it is read as text.
+fn escape(s: &str) -> String {
+    let s = s.replace('\\', "\\e").replace('"', "\\(dq");
+    // Words such as `Fl` would be read as macros, and `|` as a delimiter.
+    let mut chars = s.chars();
+    let macro_like = (2..=3).contains(&s.len()) && chars.next().is_some_and(|c| c.is_ascii_uppercase()) &&
+        chars.all(|c| c.is_ascii_lowercase());
+    if s.is_empty() || s.contains(' ') {
+        format!("\"{}\"", s)
+    } else if macro_like || DELIMITERS.contains(&&s[..]) {
+        format!("\\&{}", s)
+    } else {
+        s
+    }
+}
+
+fn token(tok: &Token, out: &mut Vec<String>) {
+    match *tok {
+        ShortOpt(ref s) => out.extend(vec!["Fl".to_string(), escape(s)]),
+        LongOpt(ref s) => out.extend(vec!["Fl".to_string(), escape(&format!("-{}", s))]),
+        Placeholder(ref s) => out.extend(vec!["Ar".to_string(), escape(s)]),
+        Text(ref s) => out.extend(vec!["Ic".to_string(), escape(s)]),
+        ref tok => out.extend(vec!["No".to_string(), escape(&tok.pretty())])
+    }
+}
+
+// Appends the words writing `expr` within a line.
+fn inline(expr: &Expr, out: &mut Vec<String>) {
+    let words = |v: &[&str], out: &mut Vec<String>| out.extend(v.iter().map(|s| s.to_string()));
+    match expr.node {
+        Tok(ref tok) => token(tok, out),
+        OptArg(ref opt, ref arg, style) => {
+            token(opt, out);
+            match style {
+                Separate => token(arg, out),
+                Attached => {
+                    words(&["Ns", "=", "Ns"], out);
+                    token(arg, out);
+                }
+                OptionalAttached => {
+                    words(&["Ns", "Oo", "=", "Ns"], out);
+                    token(arg, out);
+                    words(&["Oc"], out);
+                }
+            }
+        }
+        Seq(ref xs) | Interleave(ref xs) => {
+            for x in xs {
+                grouped(x, out);
+            }
+        }
+        Concat(ref xs) => {
+            for (i, x) in xs.iter().enumerate() {
+                if i > 0 {
+                    words(&["Ns"], out);
+                }
+                grouped(x, out);
+            }
+        }
+        Opt(ref e) => {
+            words(&["Oo"], out);
+            inline(e, out);
+            words(&["Oc"], out);
+        }
+        Repeat(ref e) => {
+            grouped(e, out);
+            words(&["..."], out);
+        }
+        Select(ref xs) => {
+            for (i, x) in xs.iter().enumerate() {
+                if i > 0 {
+                    words(&["|"], out);
+                }
+                grouped(x, out);
+            }
+        }
+    }
+}
+
+// Appends the words writing `expr`, grouped by braces if it has several
+// elements.
+fn grouped(expr: &Expr, out: &mut Vec<String>) {
+    match expr.node {
+        Seq(_) | Select(_) | Interleave(_) | OptArg(_, _, Separate) => {
+            out.push("Bro".to_string());
+            inline(expr, out);
+            out.push("Brc".to_string());
+        }
+        _ => inline(expr, out)
+    }
+}
+
+// Writes an element of the arguments as a line.
+fn element_line(expr: &Expr) -> String {
+    let mut words = vec![];
+    match expr.node {
+        Opt(ref e) => {
+            words.push("Op".to_string());
+            inline(e, &mut words);
+        }
+        Select(_) | OptArg(_, _, Separate) => grouped(expr, &mut words),
+        _ => inline(expr, &mut words)
+    }
+    format!(".{}", words.join(" "))
+}
+
+/// Returns the lines writing `cmd`, starting with `.Nm`.
+pub fn form(cmd: &Command) -> String {
+    let mut lines = vec![format!(".Nm {}", escape(&cmd.name))];
+    match cmd.args.node {
+        Seq(ref xs) | Interleave(ref xs) => lines.extend(xs.iter().map(element_line)),
+        _ => lines.push(element_line(&cmd.args))
+    }
+    let mut s = lines.join("\n");
+    s.push('\n');
+    s
+}
+
+/// Returns a SYNOPSIS section made of the forms `cmds`.
+pub fn synopsis(cmds: &[Command]) -> String {
+    let mut s = ".Sh SYNOPSIS\n".to_string();
+    for cmd in cmds {
+        s.push_str(&form(cmd));
+    }
+    s
+}
+
+// A word of a macro line.
+struct Word {
+    text: String,
+    span: Span,
+    // Whether the word is quoted or starts with `\&`, so that it is not read
+    // as a macro or a delimiter.
+    literal: bool
+}
+
+// Splits a macro line, which starts at `pos`, into words.
+fn words(line: &str, pos: Pos) -> Vec<Word> {
+    let mut words = vec![];
+    let mut word: Option<Word> = None;
+    let mut quoted = false;
+    let mut pos = pos.advance('.');
+    let mut chars = line[1..].chars().peekable();
+    while let Some(c) = chars.next() {
+        let lo = pos;
+        pos = pos.advance(c);
+        if c == '\n' || ((c == ' ' || c == '\t') && !quoted) {
+            words.extend(word.take());
+            continue
+        }
+        let w = word.get_or_insert_with(|| Word { text: String::new(), span: Span::empty(lo), literal: false });
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                pos = pos.advance(chars.next().unwrap());
+                w.text.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if w.text.is_empty() && !w.literal => {
+                quoted = true;
+                w.literal = true;
+            }
+            '\\' => {
+                let e = chars.next();
+                pos = e.map_or(pos, |e| pos.advance(e));
+                match e {
+                    Some('"') | None => {
+                        word = None;
+                        break
+                    }
+                    Some('&') => w.literal |= w.text.is_empty(),
+                    Some('-') => w.text.push('-'),
+                    Some('e') => w.text.push('\\'),
+                    Some(e) => w.text.push(e)
+                }
+            }
+            c => w.text.push(c)
+        }
+        w.span.hi = pos;
+    }
+    words.extend(word);
+    words
+}
+
+// How the arguments of the last macro are read.
+#[derive(Eq, PartialEq, Clone, Copy)]
+enum Mode {
+    Flag,
+    Arg,
+    Text,
+    // The argument of `Sm`.
+    Spacing,
+    // The argument of `Pf`, which is joined to what follows.
+    Prefix
+}
+
+// The state of reading the SYNOPSIS section of a page.
+struct Importer {
+    // The name given by the first `.Nm`, used by those without an argument.
+    name: Option<String>,
+    forms: Vec<Vec<Spanned<Token>>>,
+    // Whether the next token is written without space before it.
+    join: bool,
+    // Whether spacing is off (`.Sm off`).
+    nospace: bool
+}
+
+impl Importer {
+    fn push(&mut self, tok: Token, span: Span) {
+        let join = self.join || self.nospace;
+        self.join = false;
+        let form = match self.forms.last_mut() {
+            Some(form) => form,
+            None => return
+        };
+        // The parser concatenates the tokens whose spans are adjacent.
+        let lo = match form.last() {
+            Some(last) if join => last.span.hi,
+            _ => span.lo
+        };
+        form.push(Spanned::new(tok, Span::new(lo, span.hi)));
+    }
+
+    fn line(&mut self, words: &[Word]) {
+        let mut mode = Mode::Text;
+        // The spans of the `Op` macros, which are closed at the end of the
+        // line.
+        let mut ops = vec![];
+        // The `Fl` or `Ar` still without an argument.
+        let mut pending: Option<Span> = None;
+        for (i, word) in words.iter().enumerate() {
+            let is_macro = !word.literal && (i == 0 || CALLABLE.contains(&&word.text[..]));
+            if is_macro {
+                if let Some(span) = pending.take() {
+                    self.empty_arg(mode, span);
+                }
+            }
+            match (is_macro, &word.text[..]) {
+                (true, "Nm") if i == 0 => {
+                    self.forms.push(vec![]);
+                    mode = Mode::Text;
+                    match self.name.clone() {
+                        Some(ref name) if words.len() == 1 => self.push(Text(name.clone()), word.span),
+                        _ => {}
+                    }
+                }
+                (true, "Nm") => {
+                    mode = Mode::Text;
+                    if let Some(name) = self.name.clone() {
+                        self.push(Text(name), word.span);
+                    }
+                }
+                (true, "Fl") | (true, "Ar") => {
+                    mode = if word.text == "Fl" { Mode::Flag } else { Mode::Arg };
+                    pending = Some(word.span);
+                }
+                // No space is written after an opening bracket.
+                (true, "Op") => {
+                    self.push(LBracket, word.span);
+                    self.join = true;
+                    ops.push(word.span);
+                    mode = Mode::Text;
+                }
+                (true, "Oo") => {
+                    self.push(LBracket, word.span);
+                    self.join = true;
+                }
+                (true, "Oc") => self.push(RBracket, word.span),
+                (true, "Bro") => {
+                    self.push(LBrace, word.span);
+                    self.join = true;
+                }
+                (true, "Brc") => self.push(RBrace, word.span),
+                (true, "Ns") => self.join = true,
+                (true, "Sm") => mode = Mode::Spacing,
+                (true, "Pf") => mode = Mode::Prefix,
+                (true, "Cm") | (true, "Ic") | (true, "Li") | (true, "No") | (true, "Ev") | (true, "Pa") |
+                (true, "Sy") | (true, "Em") | (true, "Ql") => mode = Mode::Text,
+                (true, "Xo") | (true, "Xc") | (true, "Ek") => {}
+                (true, _) => {
+                    // Other macros, such as `.Pp` and `.Bk`, are ignored with
+                    // their arguments.
+                    if i == 0 {
+                        return
+                    }
+                }
+                (false, text) => {
+                    pending = None;
+                    self.arg(mode, word, text);
+                    if mode == Mode::Prefix {
+                        mode = Mode::Text;
+                    }
+                }
+            }
+        }
+        if let Some(span) = pending {
+            self.empty_arg(mode, span);
+        }
+        let end = words.last().map_or(Pos::default(), |w| w.span.hi);
+        for _ in ops {
+            self.push(RBracket, Span::empty(end));
+        }
+    }
+
+    // Reads `text`, an argument of the last macro.
+    fn arg(&mut self, mode: Mode, word: &Word, text: &str) {
+        let span = word.span;
+        if !word.literal {
+            match text {
+                "|" => return self.push(Bar, span),
+                "..." => return self.push(Dots, span),
+                "=" => return self.push(Equals, span),
+                "[" => return self.push(LBracket, span),
+                "]" => return self.push(RBracket, span),
+                "{" => return self.push(LBrace, span),
+                "}" => return self.push(RBrace, span),
+                "." | "," | ":" | ";" | ")" | "?" | "!" => {
+                    self.join = true;
+                    return self.push(Text(text.to_string()), span)
+                }
+                "(" => {
+                    self.push(Text(text.to_string()), span);
+                    self.join = true;
+                    return
+                }
+                _ => {}
+            }
+        }
+        match mode {
+            Mode::Flag => {
+                let tok = match text.strip_prefix('-') {
+                    Some(name) => LongOpt(name.to_string()),
+                    None => ShortOpt(text.to_string())
+                };
+                self.push(tok, span);
+            }
+            Mode::Arg => self.push(Placeholder(text.to_string()), span),
+            Mode::Text => self.push(Text(text.to_string()), span),
+            Mode::Spacing => self.nospace = text == "off",
+            Mode::Prefix => {
+                self.push(Text(text.to_string()), span);
+                self.join = true;
+            }
+        }
+    }
+
+    // Reads an `Fl` or `Ar` without arguments, which is written `-` or
+    // `file ...`.
+    fn empty_arg(&mut self, mode: Mode, span: Span) {
+        if mode == Mode::Flag {
+            self.push(ShortOpt(String::new()), span);
+        } else {
+            self.push(Placeholder("file".to_string()), span);
+            self.push(Dots, span);
+        }
+    }
+}
+
+// The macros which are read within a line.
+const CALLABLE: &[&str] = &[
+    "Ar", "Bro", "Brc", "Cm", "Em", "Ev", "Fl", "Ic", "Li", "Nm", "No", "Ns", "Oc", "Oo", "Op", "Pa", "Pf",
+    "Ql", "Sy", "Xc", "Xo"
+];
+
+/// Reads the forms in the SYNOPSIS section of the mdoc(7) page `page`.  Each
+/// form starts with `.Nm`.  Returns `None` if the page has no SYNOPSIS
+/// section.
+///
+/// The macros are read as the tokens of the plain notation, with `.Fl` giving
+/// options and `.Ar` placeholders, and parsed as a synopsis.  So misnested
+/// macros such as an `.Oc` without `.Oo` are reported as parse errors, whose
+/// spans are relative to `page`.
+pub fn import(page: &str) -> Option<Vec<ParseResult<Command>>> {
+    let mut importer = Importer { name: None, forms: vec![], join: false, nospace: false };
+    let mut found = false;
+    let mut in_synopsis = false;
+    let mut pos = Pos::default();
+    for line in page.split_inclusive('\n') {
+        let line_pos = pos;
+        pos = pos.advance_str(line);
+        if !line.starts_with('.') {
+            continue
+        }
+        let words = words(line, line_pos);
+        let texts = words.iter().map(|w| &w.text[..]).collect::<Vec<_>>();
+        match texts.first() {
+            Some(&"Sh") => {
+                in_synopsis = !found && texts[1..].join(" ").eq_ignore_ascii_case("SYNOPSIS");
+                found |= in_synopsis;
+                continue
+            }
+            Some(&"Nm") if importer.name.is_none() && texts.len() > 1 => {
+                importer.name = Some(texts[1].to_string())
+            }
+            _ => {}
+        }
+        if in_synopsis {
+            importer.line(&words);
+        }
+    }
+    if !found {
+        return None
+    }
+    Some(importer.forms.into_iter().map(|toks| parser::parse_command(Tokenizer::from_tokens(toks))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use ast::Command;
+    use parser;
+
+    fn parse(s: &str) -> Command { parser::parse_command(Tokenizer::new(s.chars())).unwrap().normalize() }
+
+    fn lines(s: &str) -> Vec<String> {
+        super::form(&parse(s)).lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(vec![".Nm ls", ".Op Fl a", ".Op Fl o Ar FILE", ".Ar FILE ..."],
+                   lines("ls [-a] [-o FILE] FILE..."));
+        assert_eq!(vec![".Nm cc", ".Fl -std Ns = Ns Ar STD", ".Op Fl -color Ns Oo = Ns Ar WHEN Oc"],
+                   lines("cc --std=STD [--color[=WHEN]]"));
+        assert_eq!(vec![".Nm cc", ".Op Fl I Ns Ar DIR"], lines("cc [-I{DIR}]"));
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(vec![".Nm git", ".Bro Ic add | Ic rm Brc", ".Oo Ar FILE Oc ..."],
+                   lines("git {add | rm} [FILE]..."));
+        assert_eq!(vec![".Nm cmd", ".Op Fl a Oo Fl b | Fl c Oc"], lines("cmd [-a [-b | -c]]"));
+        assert_eq!(vec![".Nm cmd", ".Bro Fl a Ar N Brc ..."], lines("cmd {-a N}..."));
+        assert_eq!(vec![".Nm cmd", ".Ic \\&Fl"], lines("cmd Fl"));
+    }
+
+    #[test]
+    fn synopsis() {
+        let cmds = vec![parse("ls [-a]"), parse("ls -d DIR")];
+        assert_eq!(".Sh SYNOPSIS\n.Nm ls\n.Op Fl a\n.Nm ls\n.Fl d\n.Ar DIR\n", super::synopsis(&cmds));
+    }
+
+    fn import(body: &str) -> Vec<Result<String, String>> {
+        let page = format!(".Dd $Mdocdate$\n.Dt SSH 1\n.Sh NAME\n.Nm ssh\n.Nd client\n.Sh SYNOPSIS\n{}.Sh DESCRIPTION\n.Nm\n",
+                           body);
+        super::import(&page).unwrap().into_iter()
+            .map(|f| f.map(|c| c.normalize().pretty()).map_err(|e| format!("{}: {}", e.span.lo, e)))
+            .collect()
+    }
+
+    #[test]
+    fn import_macros() {
+        assert_eq!(vec![Ok("ssh [-46v] [-D [<addr>{:}]<port>] [-W <host>{:}{<port>}] <dest> [<cmd> [<arg>...]]".to_string())],
+                   import(".Nm\n.Op Fl 46v\n.Op Fl D Oo Ar addr : Oc Ns Ar port\n.Op Fl W Ar host : Ns Ar port\n\
+                           .Ar dest\n.Op Ar cmd Op Ar arg ...\n"));
+        assert_eq!(vec![Ok("ssh -p [--all] {a | b}".to_string()), Ok("ssh -l <file>...".to_string())],
+                   import(".Nm ssh\n.Fl p\n.Pp\n.Op Fl -all\n.Bro Cm a | Cm b Brc\n.\\\" comment\n.Nm\n.Fl l Ar\n"));
+        assert_eq!(None, super::import(".Sh NAME\n.Nm ssh\n"));
+    }
+
+    #[test]
+    fn import_generated() {
+        for s in &["ls [-a] [-o FILE] FILE...", "git {add | rm} [FILE]...", "cc --std=STD [--color[=WHEN]]",
+                   "cmd [-a [-b | -c]] {-a N}... [-I{DIR}]", "ls {-d DIR}"] {
+            let page = super::synopsis(&[parse(s)]);
+            let forms = super::import(&page).unwrap().into_iter().map(|f| f.unwrap().normalize()).collect::<Vec<_>>();
+            assert_eq!(vec![parse(s)], forms);
+        }
+    }
+
+    #[test]
+    fn import_errors() {
+        assert_eq!(vec![Err("8:9: expected `]`, found EOF".to_string())], import(".Nm\n.Oo Fl a\n"));
+        assert_eq!(vec![Err("8:10: expected `]`, found `}`".to_string())], import(".Nm\n.Oo Fl a Brc\n"));
+        assert_eq!(vec![Err("8:7: unexpected token `]` found".to_string()), Ok("ssh".to_string())],
+                   import(".Nm\n.Fl a Oc\n.Nm\n"));
+    }
+}
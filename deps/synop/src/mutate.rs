@@ -0,0 +1,173 @@
+//! Generation of command lines a synopsis does not accept, by mutating those
+//! it accepts.
+
+use token::Token;
+use token::Token::LongOpt;
+use ast::Expr;
+use ast::ExprKind::*;
+use expand::{ExpandOptions, Word};
+use cover::cover;
+use matcher;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Mutation {
+    /// A word is removed.
+    Drop,
+    /// A word is written twice.
+    Duplicate,
+    /// Two branches of a `{X|Y}` are both taken.
+    Combine,
+    /// A word is swapped with the operand following it, or vice versa.
+    Reorder,
+    /// An option the synopsis does not have is added.
+    Inject
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Mutant {
+    pub mutation: Mutation,
+    pub args: Vec<Word>
+}
+
+fn to_strings(args: &[Word]) -> Vec<String> {
+    args.iter().map(|w| w.iter().map(|t| t.pretty()).collect()).collect()
+}
+
+/// Returns command lines which do not match `expr`, each made by mutating one
+/// matching `expr`.  The command lines mutated are those covering each choice
+/// of `expr` once, as given by `cover`.
+pub fn mutants(expr: &Expr, opts: &ExpandOptions) -> Vec<Mutant> {
+    let mut candidates = vec![];
+    let lines = cover(expr, 1, opts);
+    let unknown = unknown_option(&lines);
+    for args in &lines {
+        for i in 0..args.len() {
+            let mut v = args.clone();
+            v.remove(i);
+            candidates.push((Mutation::Drop, v));
+            let mut v = args.clone();
+            v.insert(i, args[i].clone());
+            candidates.push((Mutation::Duplicate, v));
+            if i + 1 < args.len() && args[i] != args[i + 1] {
+                let words = to_strings(&args[i..i + 2]);
+                if words.iter().any(|w| matcher::is_operand(w)) {
+                    let mut v = args.clone();
+                    v.swap(i, i + 1);
+                    candidates.push((Mutation::Reorder, v));
+                }
+            }
+        }
+        for &i in &[0, args.len()] {
+            let mut v = args.clone();
+            v.insert(i, vec![unknown.clone()]);
+            candidates.push((Mutation::Inject, v));
+        }
+    }
+    for e in combined(expr) {
+        for args in cover(&e, 1, opts) {
+            candidates.push((Mutation::Combine, args));
+        }
+    }
+
+    let mut out: Vec<Mutant> = vec![];
+    for (mutation, args) in candidates {
+        if out.iter().any(|m| m.args == args) || matcher::match_args(expr, &to_strings(&args)).is_some() {
+            continue
+        }
+        out.push(Mutant { mutation, args });
+    }
+    out
+}
+
+// Returns an option name not used in `lines`.
+fn unknown_option(lines: &[Vec<Word>]) -> Token {
+    let used = |name: &str| {
+        lines.iter().flat_map(|args| args.iter()).flat_map(|w| w.iter()).any(|t| t.pretty() == name)
+    };
+    let mut name = "--unknown".to_string();
+    let mut i = 0;
+    while used(&name) {
+        i += 1;
+        name = format!("--unknown{}", i);
+    }
+    LongOpt(name[2..].to_string())
+}
+
+// Returns copies of `expr` in which a `{X|Y}` is replaced with `X Y`, for
+// each `Select` and each pair of its branches.
+fn combined(expr: &Expr) -> Vec<Expr> {
+    let with = |node| Expr { node, span: expr.span };
+    let mut out = vec![];
+    match expr.node {
+        Tok(_) | OptArg(..) => {}
+        Seq(ref xs) | Concat(ref xs) | Select(ref xs) | Interleave(ref xs) => {
+            for (i, x) in xs.iter().enumerate() {
+                for e in combined(x) {
+                    let mut v = xs.clone();
+                    v[i] = e;
+                    out.push(with(match expr.node {
+                        Seq(_) => Seq(v),
+                        Concat(_) => Concat(v),
+                        Select(_) => Select(v),
+                        _ => Interleave(v)
+                    }));
+                }
+            }
+            if let Select(ref sel) = expr.node {
+                for i in 0..sel.len() {
+                    for j in i + 1..sel.len() {
+                        out.push(with(Seq(vec![sel[i].clone(), sel[j].clone()])));
+                    }
+                }
+            }
+        }
+        Opt(ref e) => out.extend(combined(e).into_iter().map(|e| with(Opt(Box::new(e))))),
+        Repeat(ref e) => out.extend(combined(e).into_iter().map(|e| with(Repeat(Box::new(e)))))
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use parser;
+    use matcher;
+    use expand::ExpandOptions;
+    use super::Mutation::*;
+    use super::Mutation;
+
+    fn mutants(synopsis: &str) -> Vec<(Mutation, String)> {
+        let cmd = parser::parse_command(Tokenizer::new(synopsis.chars())).unwrap().normalize();
+        let ms = super::mutants(&cmd.args, &ExpandOptions::default());
+        for m in &ms {
+            assert!(matcher::match_args(&cmd.args, &super::to_strings(&m.args)).is_none());
+        }
+        ms.into_iter().map(|m| (m.mutation, super::to_strings(&m.args).join(" "))).collect()
+    }
+
+    fn has(ms: &[(Mutation, String)], mutation: Mutation, line: &str) -> bool {
+        ms.iter().any(|&(m, ref l)| m == mutation && l == line)
+    }
+
+    #[test]
+    fn mutations() {
+        let ms = mutants("cp [-r] SRC DEST");
+        assert!(has(&ms, Drop, "SRC"));
+        assert!(!ms.iter().any(|(_, l)| l == "SRC DEST"));
+        assert!(has(&ms, Duplicate, "-r -r SRC DEST"));
+        assert!(has(&ms, Reorder, "SRC -r DEST"));
+        assert!(has(&ms, Inject, "--unknown SRC DEST"));
+        assert!(has(&ms, Inject, "SRC DEST --unknown"));
+
+        let ms = mutants("git {add | rm} [-v]... FILE");
+        assert!(has(&ms, Combine, "add rm FILE"));
+        assert!(!ms.iter().any(|(_, l)| l == "add -v -v FILE"));
+        assert!(has(&ms, Duplicate, "add add FILE"));
+    }
+
+    #[test]
+    fn unknown_option() {
+        let ms = mutants("cmd [--unknown]");
+        assert!(has(&ms, Inject, "--unknown1"));
+    }
+}
@@ -1,93 +1,301 @@
+use std::error::Error;
+use std::fmt;
 use token::{Tokenizer, Token};
 use token::Token::*;
-use ast::Expr;
-use ast::Expr::*;
+use span::{Pos, Span, Spanned};
+use ast::{Expr, Command};
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
 
-pub type ParseResult<T> = Result<T, String>;
+pub type ParseResult<T> = Result<T, ParseError>;
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum ParseErrorKind {
+    UnexpectedToken(Token),
+    UnexpectedEof,
+    /// A sequence of characters which does not form any token.
+    InvalidToken(String),
+    /// A form which does not start with the program name.  Holds the token
+    /// found instead, or `None` at EOF.
+    MissingCommandName(Option<Token>)
+}
+
+/// An error occurred while parsing a synopsis.
+///
+/// `expected` holds the tokens which would have been accepted at `span`.  It
+/// is empty if the parser has no particular token to suggest.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+    pub expected: Vec<Token>
+}
+
+impl ParseError {
+    #[inline]
+    pub fn new(kind: ParseErrorKind, span: Span, expected: Vec<Token>) -> ParseError {
+        ParseError { kind, span, expected }
+    }
+
+    fn unexpected(found: Spanned<Option<Token>>, expected: Vec<Token>) -> ParseError {
+        let kind = match found.node {
+            Some(tok) => ParseErrorKind::UnexpectedToken(tok),
+            None => ParseErrorKind::UnexpectedEof
+        };
+        ParseError::new(kind, found.span, expected)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let found = match self.kind {
+            ParseErrorKind::UnexpectedToken(ref tok) => format!("`{}`", tok.pretty()),
+            ParseErrorKind::UnexpectedEof => "EOF".to_string(),
+            ParseErrorKind::InvalidToken(ref s) => return write!(f, "invalid token `{}` found", s),
+            ParseErrorKind::MissingCommandName(Some(ref tok)) => {
+                return write!(f, "expected command name, found `{}`", tok.pretty())
+            }
+            ParseErrorKind::MissingCommandName(None) => {
+                return write!(f, "expected command name, found EOF")
+            }
+        };
+        if self.expected.is_empty() {
+            return match self.kind {
+                ParseErrorKind::UnexpectedEof => write!(f, "unexpected EOF found"),
+                _ => write!(f, "unexpected token {} found", found)
+            }
+        }
+        let mut expected = self.expected.iter()
+            .map(|tok| format!("`{}`", tok.pretty()))
+            .collect::<Vec<_>>();
+        let last = expected.pop().unwrap();
+        if expected.is_empty() {
+            write!(f, "expected {}, found {}", last, found)
+        } else {
+            write!(f, "expected {} or {}, found {}", expected.join(", "), last, found)
+        }
+    }
+}
+
+impl Error for ParseError {}
 
 pub fn parse<T: Iterator<Item = char>>(mut tokenizer: Tokenizer<T>) -> ParseResult<Expr> {
-    let (expr, next_token) = try!(parse_expr(&mut tokenizer));
-    if next_token != None {
-        return Err(unexpected_msg(&next_token.unwrap()));
+    parse_all(&mut tokenizer)
+}
+
+/// Parses a synopsis form, which starts with the program name.
+pub fn parse_command<T: Iterator<Item = char>>(mut tokenizer: Tokenizer<T>) -> ParseResult<Command> {
+    let tok = next_token(&mut tokenizer)?;
+    let name = match tok.node {
+        Some(Text(name)) => name,
+        node => {
+            return Err(ParseError::new(ParseErrorKind::MissingCommandName(node), tok.span, vec![]))
+        }
+    };
+    let args = parse_all(&mut tokenizer)?;
+    let span = tok.span.to(args.span);
+    Ok(Command::new(name, args, span))
+}
+
+fn parse_all<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>) -> ParseResult<Expr> {
+    let (expr, next_token) = parse_expr(&mut *tokenizer)?;
+    if next_token.node.is_some() {
+        return Err(ParseError::unexpected(next_token, vec![]));
     }
     Ok(expr)
 }
 
-fn parse_expr<T: Iterator<Item = Token>>(tokenizer: &mut T) -> ParseResult<(Expr, Option<Token>)> {
+fn next_token<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>)
+                                        -> ParseResult<Spanned<Option<Token>>> {
+    match tokenizer.next() {
+        Some(Ok(tok)) => Ok(Spanned::new(Some(tok.node), tok.span)),
+        Some(Err(err)) => Err(err),
+        None => Ok(Spanned::new(None, Span::empty(tokenizer.end())))
+    }
+}
+
+fn parse_expr<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>)
+                                        -> ParseResult<(Expr, Spanned<Option<Token>>)> {
     let mut v = Vec::new();
     loop {
-        let (term, n) = try!(parse_term(&mut *tokenizer));
+        let (term, n) = parse_term(&mut *tokenizer)?;
         v.push(term);
-        if n != Some(Bar) {
+        if n.node != Some(Bar) {
             if v.len() == 1 {
                 return Ok((v.pop().unwrap(), n))
             }
-            return Ok((Select(v), n))
+            let span = v[0].span.to(v[v.len() - 1].span);
+            return Ok((Expr::new(Select(v), span), n))
         }
     }
 }
 
-fn parse_term<T: Iterator<Item = Token>>(tokenizer: &mut T) -> ParseResult<(Expr, Option<Token>)> {
-    let mut v = Vec::new();
+fn parse_term<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>)
+                                        -> ParseResult<(Expr, Spanned<Option<Token>>)> {
+    let mut v: Vec<Expr> = Vec::new();
     loop {
-        match tokenizer.next() {
-            Some(LBracket) => v.push(try!(parse_bracket(&mut *tokenizer))),
-            Some(LBrace)   => v.push(try!(parse_brace(&mut *tokenizer))),
+        let tok = next_token(&mut *tokenizer)?;
+        match tok.node {
+            Some(LBracket) => {
+                let is_arg = match tokenizer.peek() {
+                    Some(Ok(next)) => next.node == Equals,
+                    _ => false
+                };
+                if is_arg {
+                    let arg = parse_optional_arg(&mut *tokenizer, tok.span, &mut v)?;
+                    v.push(arg)
+                } else {
+                    let opt = parse_bracket(&mut *tokenizer, tok.span)?;
+                    push_term(&mut v, opt)
+                }
+            }
+            Some(LBrace) => {
+                let group = parse_brace(&mut *tokenizer, tok.span)?;
+                push_term(&mut v, group)
+            }
             Some(Dots) => {
                 // Only last one element is repeated in this implementation.
                 match v.pop() {
-                    Some(last) => v.push(Repeat(Box::new(last))),
-                    None => return Err(unexpected_msg(&Dots))
+                    Some(last) => {
+                        let span = last.span.to(tok.span);
+                        v.push(Expr::new(Repeat(Box::new(last)), span))
+                    }
+                    None => return Err(ParseError::unexpected(tok, vec![]))
                 }
             },
-            Some(tok @ Text(_))
-                | Some(tok @ ShortOpt(_))
-                | Some(tok @ LongOpt(_))
-                => v.push(Tok(tok)),
-            n => {
+            Some(Equals) => {
+                let (name, span) = match take_option(&mut v, tok.span.lo) {
+                    Some(opt) => opt,
+                    None => return Err(ParseError::unexpected(tok, vec![]))
+                };
+                let arg = expect_arg(&mut *tokenizer)?;
+                v.push(Expr::new(OptArg(name, arg.node, Attached), span.to(arg.span)))
+            }
+            Some(Text(_)) | Some(Placeholder(_)) | Some(ShortOpt(_)) | Some(LongOpt(_)) => {
+                push_term(&mut v, Expr::new(Tok(tok.node.unwrap()), tok.span))
+            }
+            _ => {
+                let mut v = v;
                 if v.len() == 1 {
-                    return Ok((v.pop().unwrap(), n))
+                    return Ok((v.pop().unwrap(), tok))
                 }
-                return Ok((Seq(v), n))
+                let span = match (v.first(), v.last()) {
+                    (Some(first), Some(last)) => first.span.to(last.span),
+                    _ => Span::empty(tok.span.lo)
+                };
+                return Ok((Expr::new(Seq(v), span), tok))
             }
         }
     }
 }
 
-fn parse_bracket<T: Iterator<Item = Token>>(tokenizer: &mut T) -> ParseResult<Expr> {
-    let (expr, c) = try!(parse_expr(&mut *tokenizer));
-    try!(expect_token(&RBracket, &c));
-    Ok(Opt(Box::new(expr)))
+/// Pushes `e` to `v`.  If no whitespace separates `e` from the last element of
+/// `v`, they are concatenated instead.
+fn push_term(v: &mut Vec<Expr>, e: Expr) {
+    let adjacent = match v.last() {
+        Some(last) => last.span.hi.offset == e.span.lo.offset,
+        None => false
+    };
+    if !adjacent {
+        v.push(e);
+        return
+    }
+    let last = v.pop().unwrap();
+    let span = last.span.to(e.span);
+    let mut xs = match last.node {
+        Concat(xs) => xs,
+        node => vec![Expr::new(node, last.span)]
+    };
+    xs.push(e);
+    v.push(Expr::new(Concat(xs), span))
 }
 
-fn parse_brace<T: Iterator<Item = Token>>(tokenizer: &mut T) -> ParseResult<Expr> {
-    let (expr, c) = try!(parse_expr(&mut *tokenizer));
-    try!(expect_token(&RBrace, &c));
-    Ok(expr)
+/// Parses `[=ARG]` following an option, the `[` at `open` has already been
+/// consumed.
+fn parse_optional_arg<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>, open: Span, v: &mut Vec<Expr>)
+                                                -> ParseResult<Expr> {
+    let eq = next_token(&mut *tokenizer)?;
+    let opt = if eq.span.lo.offset == open.hi.offset { take_option(v, open.lo) } else { None };
+    let (name, span) = match opt {
+        Some(opt) => opt,
+        None => return Err(ParseError::unexpected(eq, vec![]))
+    };
+    let arg = expect_arg(&mut *tokenizer)?;
+    let close = expect_token(RBracket, next_token(&mut *tokenizer)?)?;
+    Ok(Expr::new(OptArg(name, arg.node, OptionalAttached), span.to(close)))
+}
+
+/// Removes the last element of `v` if it is an option ending at `hi`, that is,
+/// with no whitespace before what follows.
+fn take_option(v: &mut Vec<Expr>, hi: Pos) -> Option<(Token, Span)> {
+    match v.last() {
+        Some(&Expr { node: Tok(ref tok), span }) if tok.is_option() && span.hi.offset == hi.offset => {}
+        _ => return None
+    }
+    v.pop().and_then(|e| match e.node { Tok(tok) => Some((tok, e.span)), _ => None })
 }
 
-fn expect_token(expect: &Token, actual: &Option<Token>) -> ParseResult<()> {
-    match *actual {
-        Some(ref ac) => {
-            if ac != expect {
-                return Err(format!("expected `{}`, found `{}`", expect.pretty(), ac.pretty()))
+/// Pairs the option and the placeholder of a group written `[-o FILE]` or
+/// `{-e PAT}`, or of a branch of `{-e PAT | -f FILE}`.  An option followed by
+/// a placeholder elsewhere, as in `cat -n FILE`, is a flag and an operand.
+fn pair_option_arg(expr: Expr) -> Expr {
+    let span = expr.span;
+    match expr.node {
+        Seq(mut v) => {
+            let paired = match (&v[..], v.len()) {
+                (&[Expr { node: Tok(ref opt), .. }, Expr { node: Tok(Placeholder(_)), .. }], 2) => opt.is_option(),
+                _ => false
+            };
+            if !paired {
+                return Expr::new(Seq(v), span)
+            }
+            match (v.remove(0).node, v.remove(0).node) {
+                (Tok(opt), Tok(arg)) => Expr::new(OptArg(opt, arg, Separate), span),
+                _ => unreachable!()
             }
         }
-        None => return Err(format!("expected `{}`, found EOF", expect.pretty()))
+        Select(v) => Expr::new(Select(v.into_iter().map(pair_option_arg).collect()), span),
+        node => Expr::new(node, span)
+    }
+}
+
+fn expect_arg<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>)
+                                        -> ParseResult<Spanned<Token>> {
+    let tok = next_token(&mut *tokenizer)?;
+    match tok.node {
+        Some(Text(_)) | Some(Placeholder(_)) => Ok(Spanned::new(tok.node.unwrap(), tok.span)),
+        _ => Err(ParseError::unexpected(tok, vec![]))
     }
-    Ok(())
 }
 
-fn unexpected_msg(unexpect: &Token) -> String {
-    format!("unexpected token `{}` found", unexpect.pretty())
+fn parse_bracket<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>, open: Span)
+                                           -> ParseResult<Expr> {
+    let (expr, c) = parse_expr(&mut *tokenizer)?;
+    let expr = pair_option_arg(expr);
+    expect_token(RBracket, c).map(|close| Expr::new(Opt(Box::new(expr)), open.to(close)))
+}
+
+fn parse_brace<T: Iterator<Item = char>>(tokenizer: &mut Tokenizer<T>, open: Span)
+                                         -> ParseResult<Expr> {
+    let (expr, c) = parse_expr(&mut *tokenizer)?;
+    let expr = pair_option_arg(expr);
+    expect_token(RBrace, c).map(|close| Expr::new(expr.node, open.to(close)))
+}
+
+fn expect_token(expect: Token, actual: Spanned<Option<Token>>) -> ParseResult<Span> {
+    if actual.node.as_ref() != Some(&expect) {
+        return Err(ParseError::unexpected(actual, vec![expect]))
+    }
+    Ok(actual.span)
 }
 
 #[cfg(test)]
 mod tests {
     use token::Tokenizer;
     use token::Token::*;
-    use ast::Expr;
-    use ast::Expr::*;
+    use ast::{Expr, ArgStyle};
+    use ast::ExprKind::*;
+    use ast::ArgStyle::*;
 
     fn parse(s: &str) -> Expr {
         let p  = super::parse(Tokenizer::new(s.chars())).unwrap();
@@ -96,12 +304,24 @@ mod tests {
         p
     }
     fn parse_err(s: &str) -> String {
-        super::parse(Tokenizer::new(s.chars())).unwrap_err()
+        super::parse(Tokenizer::new(s.chars())).unwrap_err().to_string()
     }
 
-    fn text(s: &str) -> Expr { Tok(Text(s.to_string())) }
-    fn short(s: &str) -> Expr { Tok(ShortOpt(s.to_string())) }
-    fn long(s: &str) -> Expr { Tok(LongOpt(s.to_string())) }
+    fn text(s: &str) -> Expr { Tok(Text(s.to_string())).into() }
+    fn short(s: &str) -> Expr { Tok(ShortOpt(s.to_string())).into() }
+    fn long(s: &str) -> Expr { Tok(LongOpt(s.to_string())).into() }
+    fn ph(s: &str) -> Expr { Tok(Placeholder(s.to_string())).into() }
+    fn seq(v: Vec<Expr>) -> Expr { Seq(v).into() }
+    fn opt(e: Expr) -> Expr { Opt(Box::new(e)).into() }
+    fn rep(e: Expr) -> Expr { Repeat(Box::new(e)).into() }
+    fn sel(v: Vec<Expr>) -> Expr { Select(v).into() }
+    fn cat(v: Vec<Expr>) -> Expr { Concat(v).into() }
+    fn optarg(o: Expr, a: Expr, style: ArgStyle) -> Expr {
+        match (o.node, a.node) {
+            (Tok(o), Tok(a)) => OptArg(o, a, style).into(),
+            _ => panic!()
+        }
+    }
 
     #[test]
     fn seq_one() {
@@ -109,83 +329,132 @@ mod tests {
     }
     #[test]
     fn seq_multi() {
-        assert_eq!(Seq(vec![short("a"), short("b"), text("c"), long("foo")]),
+        assert_eq!(seq(vec![short("a"), short("b"), text("c"), long("foo")]),
                    parse("-a -b c --foo"));
     }
     #[test]
     fn seq_empty() {
-        assert_eq!(Seq(vec![]), parse(""));
+        assert_eq!(seq(vec![]), parse(""));
+    }
+    #[test]
+    fn placeholder() {
+        assert_eq!(seq(vec![text("cp"), opt(short("r")), rep(ph("SOURCE")), ph("dest")]),
+                   parse("cp [-r] SOURCE... <dest>"));
+        assert_eq!(sel(vec![ph("file"), text("file")]), parse("\\fIfile\\fR | file"));
+    }
+    #[test]
+    fn option_arg() {
+        assert_eq!(seq(vec![text("cc"), opt(optarg(short("o"), ph("FILE"), Separate)), ph("SRC")]),
+                   parse("cc [-o FILE] SRC"));
+        assert_eq!(optarg(long("output"), ph("FILE"), Attached), parse("--output=FILE"));
+        assert_eq!(optarg(long("color"), ph("WHEN"), OptionalAttached), parse("--color[=WHEN]"));
+        assert_eq!(seq(vec![optarg(short("o"), ph("FILE"), Separate), optarg(long("x"), text("y"), Attached)]),
+                   parse("{-o FILE} --x=y"));
+        assert_eq!(opt(sel(vec![optarg(short("e"), ph("PAT"), Separate), optarg(short("f"), ph("FILE"), Separate)])),
+                   parse("[-e PAT | -f FILE]"));
+        assert_eq!(seq(vec![short("f"), rep(ph("FILE"))]), parse("-f FILE..."));
+        assert_eq!(rep(optarg(short("e"), ph("PAT"), Separate)), parse("{-e PAT}..."));
+        assert_eq!(seq(vec![opt(short("v")), ph("FILE")]), parse("[-v] FILE"));
+        assert_eq!(seq(vec![text("cat"), short("n"), ph("FILE")]), parse("cat -n FILE"));
+        assert_eq!(opt(seq(vec![short("o"), ph("FILE"), short("v")])), parse("[-o FILE -v]"));
+    }
+    #[test]
+    fn option_arg_err() {
+        assert_eq!("unexpected token `=` found".to_string(), parse_err("a=b =c"));
+        assert_eq!("unexpected token `=` found".to_string(), parse_err("[=c]"));
+        assert_eq!("unexpected EOF found".to_string(), parse_err("--a="));
+        assert_eq!("unexpected token `]` found".to_string(), parse_err("--a[=]"));
+        assert_eq!("expected `]`, found `x`".to_string(), parse_err("--a[=b x]"));
+        // The argument is only attached to an option written next to it.
+        assert_eq!("unexpected token `=` found".to_string(), parse_err("--x = Y"));
+        assert_eq!("unexpected token `=` found".to_string(), parse_err("--x =Y"));
+        assert_eq!("unexpected token `=` found".to_string(), parse_err("--color [=WHEN]"));
+        assert_eq!("unexpected token `=` found".to_string(), parse_err("--color[ =WHEN]"));
+    }
+    #[test]
+    fn concat() {
+        assert_eq!(cat(vec![short("x"), opt(text("y"))]), parse("-x[y]"));
+        assert_eq!(seq(vec![short("x"), opt(text("y"))]), parse("-x [y]"));
+        assert_eq!(cat(vec![sel(vec![text("a"), text("b")]), text("file")]), parse("{a|b}file"));
+        assert_eq!(seq(vec![sel(vec![text("a"), text("b")]), text("file")]), parse("{a|b} file"));
+        assert_eq!(rep(cat(vec![short("x"), opt(text("y")), opt(text("z"))])), parse("-x[y][z]..."));
+        assert_eq!(seq(vec![text("a"), cat(vec![opt(text("b")), text("c")]), text("d")]),
+                   parse("a [b]c d"));
+        assert_eq!(cat(vec![rep(text("a")), text("b")]), parse("{a...}b"));
+        assert_eq!(cat(vec![text("x"), text("y")]), parse("x{y}"));
     }
     #[test]
     fn set_nested() {
-        assert_eq!(Seq(vec![Seq(vec![text("a"), text("b")]), text("c")]),
+        assert_eq!(seq(vec![seq(vec![text("a"), text("b")]), text("c")]),
                    parse("{a b} c"));
     }
 
     #[test]
-    fn opt() { assert_eq!(Opt(Box::new(text("aaa"))), parse("[aaa]")); }
+    fn opt_single() { assert_eq!(opt(text("aaa")), parse("[aaa]")); }
     #[test]
     fn opt_nested() {
-        assert_eq!(Opt(Box::new(Seq(vec![text("a"), Opt(Box::new(text("b"))), text("c")]))),
+        assert_eq!(opt(cat(vec![text("a"), opt(text("b")), text("c")])),
                    parse("[a[b]c]"));
-        assert_eq!(Opt(Box::new(Opt(Box::new(text("a"))))),
+        assert_eq!(opt(seq(vec![text("a"), opt(text("b")), text("c")])),
+                   parse("[a [b] c]"));
+        assert_eq!(opt(opt(text("a"))),
                    parse("[[a]]"));
     }
     #[test]
-    fn opt_empty() { assert_eq!(Opt(Box::new(Seq(vec![]))),
+    fn opt_empty() { assert_eq!(opt(seq(vec![])),
                                 parse("[]")); }
     #[test]
-    fn opt_nested_empty() { assert_eq!(Opt(Box::new(Opt(Box::new(Seq(vec![]))))),
+    fn opt_nested_empty() { assert_eq!(opt(opt(seq(vec![]))),
                                            parse("[[]]")); }
 
     #[test]
     fn repeat() {
-        assert_eq!(Seq(vec![text("aaa"), Repeat(Box::new(text("bbb")))]),
+        assert_eq!(seq(vec![text("aaa"), rep(text("bbb"))]),
                    parse("aaa bbb ..."));
-        assert_eq!(Repeat(Box::new(Repeat(Box::new(text("aaa"))))),
+        assert_eq!(rep(rep(text("aaa"))),
                    parse("aaa ... ..."));
     }
     #[test]
     fn repeat_with_group() {
-        assert_eq!(Seq(vec![text("aaa"), Repeat(Box::new(text("bbb")))]), parse("aaa {bbb}..."));
-        assert_eq!(Repeat(Box::new(Seq(vec![text("aaa"), text("bbb")]))), parse("{aaa bbb}..."));
-        assert_eq!(Repeat(Box::new(Opt(Box::new(text("aaa"))))), parse("[aaa]..."));
+        assert_eq!(seq(vec![text("aaa"), rep(text("bbb"))]), parse("aaa {bbb}..."));
+        assert_eq!(rep(seq(vec![text("aaa"), text("bbb")])), parse("{aaa bbb}..."));
+        assert_eq!(rep(opt(text("aaa"))), parse("[aaa]..."));
     }
     #[test]
     fn empty_repeat() { assert_eq!("unexpected token `...` found".to_string(), parse_err("...")); }
 
     #[test]
     fn bar() {
-        assert_eq!(Select(vec!(text("a"), text("b"))), parse("a|b"));
-        assert_eq!(Select(vec!(text("a"), text("b"), text("c"))), parse("a|b|c"));
-        assert_eq!(Seq(vec!(text("a"), Select(vec!(text("b"), text("c"))), text("d"))),
+        assert_eq!(sel(vec!(text("a"), text("b"))), parse("a|b"));
+        assert_eq!(sel(vec!(text("a"), text("b"), text("c"))), parse("a|b|c"));
+        assert_eq!(seq(vec!(text("a"), sel(vec!(text("b"), text("c"))), text("d"))),
                    parse("a { b | c } d"));
-        assert_eq!(Select(vec!(text("a"), Seq(vec!(text("b"), text("c"))), text("d"))),
+        assert_eq!(sel(vec!(text("a"), seq(vec!(text("b"), text("c"))), text("d"))),
                    parse("a|b c|d"));
-        assert_eq!(Select(vec!(Seq(vec!(text("a"), text("b"))),
-                               Seq(vec!(text("c"), text("d"))),
-                               Seq(vec!(text("e"), text("f"))))),
+        assert_eq!(sel(vec!(seq(vec!(text("a"), text("b"))),
+                               seq(vec!(text("c"), text("d"))),
+                               seq(vec!(text("e"), text("f"))))),
                    parse("a b|c d|e f"));
-        assert_eq!(Select(vec!(Opt(Box::new(text("b"))), text("ccc"))), parse("[b]|ccc"));
-        assert_eq!(Select(vec!(Opt(Box::new(text("b"))), Opt(Box::new(text("ccc"))))), parse("[b]|[ccc]"));
-        assert_eq!(Select(vec!(text("a"), Opt(Box::new(text("b"))), text("ccc"))), parse("a|[b]|ccc"));
-        assert_eq!(Select(vec!(text("a"), Opt(Box::new(Select(vec![text("b"), text("c")]))), text("ccc"))),
+        assert_eq!(sel(vec!(opt(text("b")), text("ccc"))), parse("[b]|ccc"));
+        assert_eq!(sel(vec!(opt(text("b")), opt(text("ccc")))), parse("[b]|[ccc]"));
+        assert_eq!(sel(vec!(text("a"), opt(text("b")), text("ccc"))), parse("a|[b]|ccc"));
+        assert_eq!(sel(vec!(text("a"), opt(sel(vec![text("b"), text("c")])), text("ccc"))),
                    parse("a|[b|c]|ccc"));
-        assert_eq!(Seq(vec![text("a"), Opt(Box::new(Select(vec![text("b"), text("c")]))), text("d")]),
+        assert_eq!(seq(vec![text("a"), opt(sel(vec![text("b"), text("c")])), text("d")]),
                    parse("a [b|c] d"));
     }
 
     #[test]
     fn bar_empty() {
-        assert_eq!(Select(vec!(text("a"), Seq(vec![]), text("c"))), parse("a||c"));
-        assert_eq!(Seq(vec!(text("a"), Select(vec!(Seq(vec![]), text("a"))), text("c"))),
+        assert_eq!(sel(vec!(text("a"), seq(vec![]), text("c"))), parse("a||c"));
+        assert_eq!(cat(vec!(text("a"), sel(vec!(seq(vec![]), text("a"))), text("c"))),
                    parse("a{|a}c"));
-        assert_eq!(Seq(vec!(text("a"), Select(vec!(Seq(vec![]), Seq(vec![]))), text("c"))),
+        assert_eq!(cat(vec!(text("a"), sel(vec!(seq(vec![]), seq(vec![]))), text("c"))),
                    parse("a{|}c"));
     }
     #[test]
     fn bar_nested() {
-        assert_eq!(Select(vec!(text("a"), Select(vec!(text("b"), text("c"))))),
+        assert_eq!(sel(vec!(text("a"), sel(vec!(text("b"), text("c"))))),
                    parse("a|{b|c}"));
     }
     #[test]
@@ -196,4 +465,54 @@ mod tests {
     fn unbaranced_parens() { assert_eq!("expected `]`, found `}`".to_string(), parse_err("[a b}")); }
     #[test]
     fn close_only() { assert_eq!("unexpected token `}` found".to_string(), parse_err("a }")) }
+
+    #[test]
+    fn invalid_token() {
+        assert_eq!("invalid token `..` found".to_string(), parse_err("a [..]"));
+        assert_eq!("invalid token `....` found".to_string(), parse_err("a ...."));
+    }
+
+    #[test]
+    fn command() {
+        fn command(s: &str) -> (String, Expr) {
+            let cmd = super::parse_command(Tokenizer::new(s.chars())).unwrap();
+            (cmd.name, cmd.args)
+        }
+        fn command_err(s: &str) -> String {
+            super::parse_command(Tokenizer::new(s.chars())).unwrap_err().to_string()
+        }
+        assert_eq!(("ls".to_string(), seq(vec![])), command("ls"));
+        assert_eq!(("ls".to_string(), seq(vec![opt(short("l")), ph("FILE")])), command("ls [-l] FILE"));
+        assert_eq!(("tar".to_string(), short("c")), command("\\fBtar\\fR -c"));
+        assert_eq!("expected command name, found `[`".to_string(), command_err("[-l] FILE"));
+        assert_eq!("expected command name, found `FILE`".to_string(), command_err("FILE"));
+        assert_eq!("expected command name, found EOF".to_string(), command_err(""));
+        assert_eq!("unexpected token `]` found".to_string(), command_err("ls ]"));
+    }
+
+    #[test]
+    fn error_span() {
+        fn check(lo: usize, hi: usize, s: &str) {
+            let err = super::parse(Tokenizer::new(s.chars())).unwrap_err();
+            assert_eq!((lo, hi), (err.span.lo.offset, err.span.hi.offset));
+        }
+        check(4, 4, "{a b");
+        check(4, 4, "{a b\n\n");
+        check(4, 5, "[a b}");
+        check(2, 3, "a }");
+        check(4, 7, "a | ...");
+    }
+
+    #[test]
+    fn expr_span() {
+        fn check(lo: usize, hi: usize, s: &str) {
+            let expr = super::parse(Tokenizer::new(s.chars())).unwrap();
+            assert_eq!((lo, hi), (expr.span.lo.offset, expr.span.hi.offset));
+        }
+        check(0, 2, "-a");
+        check(1, 9, " -a [ b ]  ");
+        check(0, 10, "{a | b}...");
+        check(2, 7, "  a|b|c");
+        check(0, 0, " ");
+    }
 }
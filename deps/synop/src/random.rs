@@ -0,0 +1,201 @@
+//! Random sampling of the command lines a synopsis accepts.
+
+use std::collections::BTreeMap;
+use token::Token::Equals;
+use ast::Expr;
+use ast::ExprKind::*;
+use ast::ArgStyle::*;
+use expand::Word;
+
+/// A small pseudo-random number generator (SplitMix64).  The numbers it
+/// yields depend only on the seed, on every platform.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng { Rng { state: seed } }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`.  `n` must not be zero.
+    pub fn below(&mut self, n: usize) -> usize { (self.next_u64() % n as u64) as usize }
+
+    /// Returns `true` with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        // The 53 high bits make a uniform number in `[0, 1)`.
+        self.unit() < p
+    }
+
+    /// Returns an index of `weights`, chosen with probability proportional
+    /// to its weight.  Weights must not be negative, and at least one must be
+    /// positive.
+    pub fn weighted(&mut self, weights: &[f64]) -> usize {
+        let mut x = self.unit() * weights.iter().sum::<f64>();
+        for (i, &w) in weights.iter().enumerate() {
+            if x < w {
+                return i
+            }
+            x -= w;
+        }
+        // Rounding may leave `x` past the last positive weight.
+        weights.iter().rposition(|&w| w > 0.0).unwrap_or(0)
+    }
+
+    // The 53 high bits make a uniform number in `[0, 1)`.
+    fn unit(&mut self) -> f64 { (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct RandomOptions {
+    /// The probability an optional element is present.
+    pub opt: f64,
+    /// The probability `X...` is repeated once more, after each time.
+    pub repeat: f64,
+    /// The maximum number of times `X...` or `[X]...` is repeated.  `X...`
+    /// is present at least once even if it is zero.
+    pub repeat_max: usize,
+    /// The weights of `Select` branches, keyed by the branch as written in
+    /// the synopsis (e.g. `add` in `{add | rm}`).  Other branches weigh 1.
+    pub weights: BTreeMap<String, f64>
+}
+
+impl Default for RandomOptions {
+    fn default() -> RandomOptions {
+        RandomOptions { opt: 0.5, repeat: 0.5, repeat_max: 8, weights: BTreeMap::new() }
+    }
+}
+
+/// Returns a command line matching `expr`, chosen at random.  `Select`
+/// branches are chosen by their weights in `opts`, and the order of
+/// `Interleave` elements uniformly.
+pub fn sample(expr: &Expr, opts: &RandomOptions, rng: &mut Rng) -> Vec<Word> {
+    let mut words = vec![];
+    sample_into(expr, opts, rng, &mut words);
+    words
+}
+
+fn sample_into(expr: &Expr, opts: &RandomOptions, rng: &mut Rng, words: &mut Vec<Word>) {
+    match expr.node {
+        Tok(ref tok) => words.push(vec![tok.clone()]),
+        OptArg(ref opt, ref arg, Separate) => {
+            words.push(vec![opt.clone()]);
+            words.push(vec![arg.clone()]);
+        }
+        OptArg(ref opt, _, OptionalAttached) if !rng.chance(opts.opt) => words.push(vec![opt.clone()]),
+        OptArg(ref opt, ref arg, _) => words.push(vec![opt.clone(), Equals, arg.clone()]),
+        Seq(ref seq) => {
+            for e in seq {
+                sample_into(e, opts, rng, words);
+            }
+        }
+        Concat(ref seq) => {
+            let mut parts = vec![];
+            for e in seq {
+                sample_into(e, opts, rng, &mut parts);
+            }
+            let word = parts.into_iter().flatten().collect::<Word>();
+            if !word.is_empty() {
+                words.push(word);
+            }
+        }
+        Opt(ref e) => {
+            if rng.chance(opts.opt) {
+                sample_into(e, opts, rng, words);
+            }
+        }
+        Repeat(ref e) => {
+            let body = match e.node {
+                Opt(_) if !rng.chance(opts.opt) => return,
+                Opt(ref body) => body,
+                _ => e
+            };
+            let mut n = 1;
+            while n < opts.repeat_max && rng.chance(opts.repeat) {
+                n += 1;
+            }
+            for _ in 0..n {
+                sample_into(body, opts, rng, words);
+            }
+        }
+        Select(ref sel) => {
+            let weights = sel.iter()
+                .map(|e| opts.weights.get(&e.pretty()).cloned().unwrap_or(1.0))
+                .collect::<Vec<_>>();
+            let i = if weights.iter().all(|&w| w == 1.0) || weights.iter().all(|&w| w == 0.0) {
+                rng.below(sel.len())
+            } else {
+                rng.weighted(&weights)
+            };
+            sample_into(&sel[i], opts, rng, words);
+        }
+        Interleave(ref xs) => {
+            let mut order = (0..xs.len()).collect::<Vec<_>>();
+            for i in (1..order.len()).rev() {
+                order.swap(i, rng.below(i + 1));
+            }
+            for i in order {
+                sample_into(&xs[i], opts, rng, words);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use ast::Command;
+    use parser;
+    use matcher;
+    use super::{Rng, RandomOptions};
+
+    fn parse(s: &str) -> Command { parser::parse_command(Tokenizer::new(s.chars())).unwrap().normalize() }
+
+    fn samples(cmd: &Command, opts: &RandomOptions, seed: u64) -> Vec<Vec<String>> {
+        let mut rng = Rng::new(seed);
+        (0..50).map(|_| {
+            super::sample(&cmd.args, opts, &mut rng).iter()
+                .map(|w| w.iter().map(|t| t.pretty()).collect())
+                .collect()
+        }).collect()
+    }
+
+    #[test]
+    fn seeded() {
+        let cmd = parse("cmd [-a] [-l] {add|rm} [-o FILE] [--color[=WHEN]] FILE...").infer_unordered();
+        let opts = RandomOptions::default();
+        assert_eq!(samples(&cmd, &opts, 42), samples(&cmd, &opts, 42));
+        assert!(samples(&cmd, &opts, 42) != samples(&cmd, &opts, 43));
+        for args in samples(&cmd, &opts, 42) {
+            assert!(matcher::match_args(&cmd.args, &args).is_some(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn probabilities() {
+        let cmd = parse("cmd [-a] [-b]... C...");
+        let never = RandomOptions { opt: 0.0, repeat: 0.0, repeat_max: 8, ..Default::default() };
+        assert!(samples(&cmd, &never, 1).iter().all(|args| *args == ["C"]));
+        let always = RandomOptions { opt: 1.0, repeat: 1.0, repeat_max: 3, ..Default::default() };
+        assert!(samples(&cmd, &always, 1).iter().all(|args| *args == ["-a", "-b", "-b", "-b", "C", "C", "C"]));
+    }
+
+    #[test]
+    fn weights() {
+        let cmd = parse("git {add | rm | mv} FILE");
+        let mut opts = RandomOptions::default();
+        opts.weights.insert("add".to_string(), 0.0);
+        opts.weights.insert("rm".to_string(), 0.0);
+        assert!(samples(&cmd, &opts, 1).iter().all(|args| args[0] == "mv"));
+        opts.weights.insert("rm".to_string(), 9.0);
+        let rm = samples(&cmd, &opts, 1).iter().filter(|args| args[0] == "rm").count();
+        assert!(rm > 35, "{} of 50", rm);
+    }
+}
@@ -0,0 +1,629 @@
+//! Generation of man(7) SYNOPSIS sections.
+//!
+//! Each form is a `.SY`/`.YS` block with an element of the arguments per
+//! line.  Options and keywords are set in bold, placeholders in italics and
+//! the brackets, braces, bars and dots in roman.  An element is written with
+//! `.OP` if it is an optional option, with a font alternation macro such as
+//! `.RB` or `.BI` if it uses at most two fonts, and with `\f` escapes
+//! otherwise.
+
+use std::iter;
+use std::slice;
+use token::{Token, Tokenizer};
+use token::Token::*;
+use ast::Command;
+use span::{Pos, Span, Spanned};
+use parser::{self, ParseError, ParseResult};
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+enum Font {
+    Bold,
+    Italic,
+    Roman
+}
+
+impl Font {
+    fn letter(self) -> char {
+        match self {
+            Font::Bold => 'B',
+            Font::Italic => 'I',
+            Font::Roman => 'R'
+        }
+    }
+}
+
+// Returns the text and the font of `tok`.
+fn styled(tok: &Token) -> (String, Font) {
+    match *tok {
+        Placeholder(ref name) => (name.clone(), Font::Italic),
+        Text(_) | ShortOpt(_) | LongOpt(_) | Equals => (tok.pretty(), Font::Bold),
+        _ => (tok.pretty(), Font::Roman)
+    }
+}
+
+// Escapes the characters of `s` special to roff.
+fn escape(s: &str) -> String { s.replace('\\', "\\e").replace('-', "\\-") }
+
+// Quotes `s` as a macro argument.
+fn quote_arg(s: &str) -> String {
+    let s = s.replace('"', "\\(dq");
+    if s.is_empty() || s.contains(' ') { format!("\"{}\"", s) } else { s }
+}
+
+// Prevents a text line from being read as a request.
+fn text_line(s: String) -> String {
+    if s.starts_with('.') || s.starts_with('\'') { format!("\\&{}", s) } else { s }
+}
+
+// Splits the tokens of `s`, a synopsis in the canonical form, into its
+// top-level elements, each a list of styled pieces.
+fn elements(s: &str) -> Vec<Vec<(String, Font)>> {
+    let mut elems: Vec<Vec<(String, Font)>> = vec![];
+    let mut depth = 0usize;
+    let mut prev_hi = None;
+    for tok in Tokenizer::new(s.chars()).filter_map(Result::ok) {
+        let gap = prev_hi.is_some_and(|hi: Pos| tok.span.lo.offset > hi.offset);
+        if elems.is_empty() || (gap && depth == 0) {
+            elems.push(vec![]);
+        } else if gap {
+            elems.last_mut().unwrap().push((" ".to_string(), Font::Roman));
+        }
+        match tok.node {
+            LBracket | LBrace => depth += 1,
+            RBracket | RBrace => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        elems.last_mut().unwrap().push(styled(&tok.node));
+        prev_hi = Some(tok.span.hi);
+    }
+    elems
+}
+
+// Writes an element as a line.
+fn element_line(pieces: &[(String, Font)]) -> String {
+    // `[-o FILE]` and `[-v]`
+    let texts = pieces.iter().map(|(t, _)| &t[..]).collect::<Vec<_>>();
+    let fonts = pieces.iter().map(|&(_, f)| f).collect::<Vec<_>>();
+    if texts.first() == Some(&"[") && texts.last() == Some(&"]") && texts.len() >= 3 &&
+        fonts[1] == Font::Bold && texts[1].starts_with('-') {
+        let inner = &pieces[1..pieces.len() - 1];
+        match inner.len() {
+            1 => return format!(".OP {}", quote_arg(&escape(&inner[0].0))),
+            3 if inner[1].0 == " " && inner[2].1 == Font::Italic => {
+                return format!(".OP {} {}", quote_arg(&escape(&inner[0].0)), quote_arg(&escape(&inner[2].0)))
+            }
+            _ => {}
+        }
+    }
+
+    // Merge the pieces in the same font.
+    let mut runs: Vec<(String, Font)> = vec![];
+    for &(ref text, font) in pieces {
+        match runs.last_mut() {
+            Some(&mut (ref mut t, f)) if f == font => t.push_str(text),
+            _ => runs.push((text.clone(), font))
+        }
+    }
+    let mut used = runs.iter().map(|&(_, f)| f).collect::<Vec<_>>();
+    used.sort_by_key(|f| f.letter());
+    used.dedup();
+    match used.len() {
+        1 if runs[0].1 == Font::Roman => text_line(escape(&runs[0].0)),
+        1 => format!(".{} {}", runs[0].1.letter(), quote_arg(&escape(&runs[0].0))),
+        2 => {
+            let args = runs.iter().map(|(t, _)| quote_arg(&escape(t))).collect::<Vec<_>>();
+            format!(".{}{} {}", runs[0].1.letter(), runs[1].1.letter(), args.join(" "))
+        }
+        _ => {
+            let text = runs.iter()
+                .map(|&(ref t, f)| format!("\\f{}{}", f.letter(), escape(t)))
+                .collect::<String>();
+            text_line(format!("{}\\fR", text))
+        }
+    }
+}
+
+/// Returns the `.SY`/`.YS` block of `cmd`.
+pub fn form(cmd: &Command) -> String {
+    let mut lines = vec![format!(".SY {}", quote_arg(&escape(&cmd.name)))];
+    let pretty = cmd.pretty();
+    let args = &pretty[cmd.name.len()..];
+    lines.extend(elements(args).iter().map(|e| element_line(e)));
+    lines.push(".YS".to_string());
+    let mut s = lines.join("\n");
+    s.push('\n');
+    s
+}
+
+/// Returns a SYNOPSIS section made of the forms `cmds`.
+pub fn synopsis(cmds: &[Command]) -> String {
+    let mut s = ".SH SYNOPSIS\n".to_string();
+    for cmd in cmds {
+        s.push_str(&form(cmd));
+    }
+    s
+}
+
+// A character of the page, with its span.
+type SrcChar = (char, Span);
+
+// Returns the characters of `line`, which starts at `pos`.
+fn src_chars(line: &str, pos: Pos) -> Vec<SrcChar> {
+    let mut pos = pos;
+    line.chars().map(|c| {
+        let lo = pos;
+        pos = pos.advance(c);
+        (c, Span::new(lo, pos))
+    }).collect()
+}
+
+// A character of a form, in its font.
+#[derive(Clone, Copy)]
+struct Piece {
+    c: char,
+    font: Font,
+    span: Span
+}
+
+// The state of reading the SYNOPSIS section of a page.
+struct Reader {
+    // The characters of each form.
+    forms: Vec<Vec<Piece>>,
+    font: Font,
+    prev_font: Font,
+    // Whether the last line ended with `\c`, which joins it to the next.
+    join: bool,
+    // Whether in no-fill mode (`.nf`), where each line not indented starts a
+    // form.
+    nofill: bool
+}
+
+impl Reader {
+    fn new() -> Reader {
+        Reader { forms: vec![vec![]], font: Font::Roman, prev_font: Font::Roman, join: false, nofill: false }
+    }
+
+    fn push(&mut self, c: char, span: Span) {
+        let font = self.font;
+        let form = self.forms.last_mut().unwrap();
+        // The parser concatenates the tokens whose spans are adjacent, so the
+        // characters of a word are made adjacent across escapes and macros:
+        // the previous one is extended up to this one.
+        let mut span = span;
+        match form.last_mut() {
+            Some(last) if !last.c.is_whitespace() && !c.is_whitespace() => {
+                if last.span.hi.offset <= span.lo.offset {
+                    last.span.hi = span.lo;
+                } else {
+                    span.lo = last.span.hi;
+                    if span.hi.offset < span.lo.offset {
+                        span.hi = span.lo;
+                    }
+                }
+            }
+            _ => {}
+        }
+        form.push(Piece { c, font, span });
+    }
+
+    fn push_str(&mut self, s: &str, span: Span) {
+        for c in s.chars() {
+            self.push(c, span);
+        }
+    }
+
+    // Separates words.  Spaces are not read as tokens, so have no span.
+    fn space(&mut self) { self.push(' ', Span::default()) }
+
+    fn break_form(&mut self) {
+        if self.forms.last().unwrap().iter().any(|p| !p.c.is_whitespace()) {
+            self.forms.push(vec![]);
+        }
+    }
+
+    fn end_line(&mut self) {
+        if !self.join {
+            self.space();
+        }
+    }
+
+    // Reads `s`, which may contain escapes, in the current font.
+    fn text(&mut self, s: &[SrcChar]) {
+        self.join = false;
+        let end = s.last().map_or(Pos::default(), |&(_, span)| span.hi);
+        let mut chars = s.iter();
+        while let Some(&(c, span)) = chars.next() {
+            if c != '\\' {
+                self.push(c, span);
+                continue
+            }
+            let e = chars.next().map(|&(e, _)| e);
+            // The span of the escape, once read.
+            let escape = |chars: &slice::Iter<SrcChar>| {
+                Span::new(span.lo, chars.as_slice().first().map_or(end, |&(_, span)| span.lo))
+            };
+            match e {
+                Some('f') => {
+                    let font = match &escape_name(&mut chars)[..] {
+                        "P" | "" => self.prev_font,
+                        name if name.contains('I') => Font::Italic,
+                        name if name.contains('B') => Font::Bold,
+                        _ => Font::Roman
+                    };
+                    self.prev_font = self.font;
+                    self.font = font;
+                }
+                Some('(') => {
+                    let name = chars.by_ref().take(2).map(|&(c, _)| c).collect::<String>();
+                    self.push_str(special_char(&name), escape(&chars));
+                }
+                Some('[') => {
+                    let name = chars.by_ref().take_while(|&&(c, _)| c != ']').map(|&(c, _)| c).collect::<String>();
+                    self.push_str(special_char(&name), escape(&chars));
+                }
+                Some('*') => {
+                    escape_name(&mut chars);
+                }
+                Some('s') => {
+                    let rest = chars.as_slice();
+                    let sign = rest.iter().take_while(|&&(c, _)| c == '+' || c == '-').count();
+                    let digits = rest[sign..].iter().take_while(|&&(c, _)| c.is_ascii_digit()).count();
+                    chars = rest[sign + digits..].iter();
+                }
+                Some('"') => break,
+                Some('c') => self.join = chars.as_slice().is_empty(),
+                Some('-') => self.push('-', escape(&chars)),
+                Some('e') | Some('\\') => self.push('\\', escape(&chars)),
+                Some(' ') | Some('~') | Some('0') => self.space(),
+                Some(c) if "&,/|^:)".contains(c) => {}
+                Some(c) => self.push(c, escape(&chars)),
+                None => {}
+            }
+        }
+    }
+
+    // Reads `s` in `font`, leaving the current font unchanged.
+    fn styled_text(&mut self, font: Font, s: &[SrcChar]) {
+        let saved = (self.font, self.prev_font);
+        self.font = font;
+        self.text(s);
+        self.font = saved.0;
+        self.prev_font = saved.1;
+    }
+
+    // Reads the request `name`, written at `span`, with its arguments.
+    fn request(&mut self, name: &str, span: Span, args: &[Vec<SrcChar>]) {
+        let font = |c| match c {
+            'B' => Font::Bold,
+            'I' => Font::Italic,
+            _ => Font::Roman
+        };
+        match name {
+            "B" | "I" | "SB" => {
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.space();
+                    }
+                    self.styled_text(font(name.chars().last().unwrap()), arg);
+                }
+                self.end_line();
+            }
+            "BI" | "BR" | "IB" | "IR" | "RB" | "RI" => {
+                let fonts = name.chars().map(font).collect::<Vec<_>>();
+                for (i, arg) in args.iter().enumerate() {
+                    self.styled_text(fonts[i % 2], arg);
+                }
+                self.end_line();
+            }
+            "SY" => {
+                self.break_form();
+                self.styled_text(Font::Bold, args.first().map_or(&[][..], |a| &a[..]));
+                self.end_line();
+            }
+            "OP" => {
+                // The brackets are given the span of the request.
+                let saved = self.font;
+                self.font = Font::Roman;
+                self.push('[', span);
+                self.styled_text(Font::Bold, args.first().map_or(&[][..], |a| &a[..]));
+                if let Some(arg) = args.get(1) {
+                    self.space();
+                    self.styled_text(Font::Italic, arg);
+                }
+                let end = args.last().and_then(|a| a.last()).map_or(span.hi, |&(_, s)| s.hi);
+                self.push(']', Span::empty(end));
+                self.font = saved;
+                self.end_line();
+            }
+            "YS" | "br" | "sp" | "PP" | "LP" | "P" | "HP" | "TP" | "IP" => self.break_form(),
+            "nf" => self.nofill = true,
+            "fi" => self.nofill = false,
+            _ => {}
+        }
+    }
+
+    fn text_line(&mut self, line: &[SrcChar]) {
+        let blank = line.iter().all(|&(c, _)| c.is_whitespace());
+        let indented = line.first().is_some_and(|&(c, _)| c.is_whitespace());
+        if blank || (self.nofill && !indented) {
+            self.break_form();
+        }
+        self.text(line);
+        self.end_line();
+    }
+}
+
+// Reads the name of an escape such as `\fB`, `\f(BI` or `\f[BI]`.
+fn escape_name(chars: &mut slice::Iter<SrcChar>) -> String {
+    match chars.next().map(|&(c, _)| c) {
+        Some('(') => chars.take(2).map(|&(c, _)| c).collect(),
+        Some('[') => chars.take_while(|&&(c, _)| c != ']').map(|&(c, _)| c).collect(),
+        Some(c) => c.to_string(),
+        None => String::new()
+    }
+}
+
+// Returns the text of a special character such as `\(dq`.
+fn special_char(name: &str) -> &'static str {
+    match name {
+        "dq" | "lq" | "rq" => "\"",
+        "aq" | "oq" | "cq" => "'",
+        "mi" | "hy" | "en" | "em" => "-",
+        "rs" => "\\",
+        "ba" => "|",
+        _ => ""
+    }
+}
+
+// Splits a request line into its name, the span of the name and the
+// arguments.  Returns `None` if `line` is a text line.
+fn parse_request(line: &[SrcChar]) -> Option<(String, Span, Vec<Vec<SrcChar>>)> {
+    let rest = match line.first() {
+        Some(&('.', _)) | Some(&('\'', _)) => &line[1..],
+        _ => return None
+    };
+    let mut args = vec![];
+    let mut arg: Option<Vec<SrcChar>> = None;
+    let mut quoted = false;
+    let mut chars = rest.iter().cloned().peekable();
+    while let Some((c, span)) = chars.next() {
+        let next = chars.peek().map(|&(c, _)| c);
+        match c {
+            '\\' if next == Some('"') => break,
+            '\\' => {
+                let arg = arg.get_or_insert_with(Vec::new);
+                arg.push((c, span));
+                arg.extend(chars.next());
+            }
+            '"' if quoted => {
+                if next == Some('"') {
+                    let (_, hi) = chars.next().unwrap();
+                    arg.get_or_insert_with(Vec::new).push(('"', span.to(hi)));
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if arg.is_none() => {
+                quoted = true;
+                arg = Some(vec![]);
+            }
+            ' ' | '\t' if !quoted => args.extend(arg.take()),
+            c => arg.get_or_insert_with(Vec::new).push((c, span))
+        }
+    }
+    args.extend(arg);
+    if args.is_empty() {
+        return Some((String::new(), Span::default(), args))
+    }
+    let name = args.remove(0);
+    let span = match (name.first(), name.last()) {
+        (Some(&(_, lo)), Some(&(_, hi))) => lo.to(hi),
+        _ => Span::default()
+    };
+    Some((name.iter().map(|&(c, _)| c).collect(), span, args))
+}
+
+fn is_word_char(c: char) -> bool { !c.is_whitespace() && !"[]{}|".contains(c) }
+
+// Reads the tokens of a form.  Words set in italics are placeholders, and
+// words set in bold keywords or options; roman words are read as in the plain
+// notation.  The words in the font of the command name, up to the first word
+// in another font, are read literally, as the name of a subcommand may be set
+// in the same font.
+fn tokens(form: &[Piece]) -> Result<Vec<Spanned<Token>>, ParseError> {
+    let name_font = form.iter().find(|p| !p.c.is_whitespace()).map(|p| p.font);
+    let name_end = form.iter().position(|p| !p.c.is_whitespace() && Some(p.font) != name_font)
+        .unwrap_or(form.len());
+    let mut toks = vec![];
+    let mut i = 0;
+    while i < form.len() {
+        let p = form[i];
+        let tok = match p.c {
+            '[' => LBracket,
+            ']' => RBracket,
+            '{' => LBrace,
+            '}' => RBrace,
+            '|' => Bar,
+            c if c.is_whitespace() => {
+                i += 1;
+                continue
+            }
+            _ => {
+                // A run of word characters in the same font.
+                let n = form[i..].iter().take_while(|q| q.font == p.font && is_word_char(q.c)).count();
+                word_tokens(&form[i..i + n], i < name_end, &mut toks)?;
+                i += n;
+                continue
+            }
+        };
+        toks.push(Spanned::new(tok, p.span));
+        i += 1;
+    }
+    Ok(toks)
+}
+
+// Reads `pieces`, characters in the same font, as tokens.
+fn word_tokens(pieces: &[Piece], literal: bool, toks: &mut Vec<Spanned<Token>>) -> Result<(), ParseError> {
+    let text = pieces.iter().map(|p| p.c).collect::<String>();
+    // The piece of each byte of `text`.
+    let index = pieces.iter().enumerate()
+        .flat_map(|(k, p)| iter::repeat_n(k, p.c.len_utf8()))
+        .collect::<Vec<_>>();
+    let span = |lo: usize, hi: usize| {
+        let end = pieces.last().unwrap().span.hi;
+        let lo = index.get(lo).map_or(end, |&k| pieces[k].span.lo);
+        Span::new(lo, if hi > 0 { pieces[index[hi - 1]].span.hi } else { lo })
+    };
+    let font = pieces[0].font;
+    let body = text.trim_end_matches('.').len();
+    let dots = text.len() - body;
+    // Options are read as they are, as some pages set them in italics.
+    if font == Font::Italic && !literal && body > 0 && !text.starts_with('-') {
+        // `file..` keeps its dots, as in the plain notation.
+        let body = body + dots % 3;
+        toks.push(Spanned::new(Placeholder(text[..body].to_string()), span(0, body)));
+        for k in 0..dots / 3 {
+            let lo = body + 3 * k;
+            toks.push(Spanned::new(Dots, span(lo, lo + 3)));
+        }
+        return Ok(())
+    }
+    for tok in Tokenizer::new(text.chars()) {
+        let tok = tok.map_err(|mut err| {
+            err.span = span(err.span.lo.offset, err.span.hi.offset);
+            err
+        })?;
+        let node = match tok.node {
+            Placeholder(_) if literal || font == Font::Bold => Text(tok.node.pretty()),
+            node => node
+        };
+        toks.push(Spanned::new(node, span(tok.span.lo.offset, tok.span.hi.offset)));
+    }
+    Ok(())
+}
+
+/// Reads the forms in the SYNOPSIS section of the man(7) page `page`.
+/// Returns `None` if the page has no SYNOPSIS section.
+///
+/// The fonts tell keywords and options, in bold, from placeholders, in
+/// italics.  The words are read as the tokens of the plain notation and
+/// parsed as a synopsis, so errors are reported with spans relative to
+/// `page`.
+pub fn import(page: &str) -> Option<Vec<ParseResult<Command>>> {
+    let mut reader = Reader::new();
+    let mut found = false;
+    let mut in_synopsis = false;
+    let mut pos = Pos::default();
+    for line in page.split_inclusive('\n') {
+        let line_pos = pos;
+        pos = pos.advance_str(line);
+        let chars = src_chars(line.trim_end_matches(['\n', '\r']), line_pos);
+        match parse_request(&chars) {
+            Some((ref name, _, ref args)) if name == "SH" => {
+                let title = args.iter().map(|a| a.iter().map(|&(c, _)| c).collect::<String>()).collect::<Vec<_>>();
+                in_synopsis = !found && title.join(" ").eq_ignore_ascii_case("SYNOPSIS");
+                found |= in_synopsis;
+            }
+            Some((ref name, span, ref args)) if in_synopsis => reader.request(name, span, args),
+            None if in_synopsis => reader.text_line(&chars),
+            _ => {}
+        }
+    }
+    if !found {
+        return None
+    }
+    Some(reader.forms.iter()
+         .filter(|form| form.iter().any(|p| !p.c.is_whitespace()))
+         .map(|form| tokens(form).and_then(|toks| parser::parse_command(Tokenizer::from_tokens(toks))))
+         .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use token::Tokenizer;
+    use token::Token::*;
+    use ast::{Command, Expr};
+    use ast::ExprKind::*;
+    use span::Span;
+    use parser;
+
+    fn parse(s: &str) -> Command { parser::parse_command(Tokenizer::new(s.chars())).unwrap().normalize() }
+
+    fn lines(s: &str) -> Vec<String> {
+        let form = super::form(&parse(s));
+        form.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(vec![".SY ls", ".OP \\-a", ".OP \\-o", ".I file", ".YS"], lines("ls [-a] [-o] <file>"));
+        assert_eq!(vec![".SY cc", ".OP \\-o FILE", ".BI \\-\\-std= STD", ".YS"],
+                   lines("cc [-o FILE] --std=STD"));
+        assert_eq!(vec![".SY ls", "\\fB\\-\\-color\\fR[\\fB=\\fIWHEN\\fR]\\fR", ".YS"], lines("ls --color[=WHEN]"));
+    }
+
+    #[test]
+    fn fonts() {
+        assert_eq!(vec![".SY git", ".RB { add \" | \" rm }", ".RI [ FILE ]...", ".YS"],
+                   lines("git {add | rm} [FILE]..."));
+        assert_eq!(vec![".SY cmd", "\\fR[\\fB\\-v\\fR | \\fIN\\fR]\\fR", ".YS"], lines("cmd [-v | N]"));
+        assert_eq!(vec![".SY cmd", ".RB [ \\-a \" \" \\-b ]", ".YS"], lines("cmd [-a -b]"));
+        assert_eq!(vec![".SY cmd", ".YS"], lines("cmd"));
+    }
+
+    #[test]
+    fn synopsis() {
+        let cmds = vec![parse("ls [-a]"), parse("ls -d DIR")];
+        assert_eq!(".SH SYNOPSIS\n.SY ls\n.OP \\-a\n.YS\n.SY ls\n.B \\-d\n.I DIR\n.YS\n", super::synopsis(&cmds));
+    }
+
+    fn import(body: &str) -> Vec<Result<String, String>> {
+        let page = format!(".TH LS 1\n.SH NAME\nls \\- list\n.SH \"SYNOPSIS\"\n{}.SH DESCRIPTION\n.B x\n", body);
+        super::import(&page).unwrap().into_iter()
+            .map(|f| f.map(|c| c.normalize().pretty()).map_err(|e| format!("{}: {}", e.span.lo, e)))
+            .collect()
+    }
+
+    fn ok(forms: &[&str]) -> Vec<Result<String, String>> { forms.iter().map(|f| Ok(f.to_string())).collect() }
+
+    #[test]
+    fn import_fonts() {
+        assert_eq!(ok(&["ls [-a] [<file>]..."]), import(".B ls\n.RB [ \\-a ]\n[\\fIfile\\fR]...\n"));
+        assert_eq!(ok(&["cp [-T] <src>"]), import(".B cp\n[\\fI\\,-T\\/\\fR] \\fIsrc\\fP\n"));
+        assert_eq!(ok(&["ls --color[=WHEN]", "ls -d"]),
+                   import(".B ls\n\\fB\\-\\-color\\fR[\\fB=\\fIWHEN\\fR]\n.br\n.B ls \\-d\n"));
+        assert_eq!(ok(&["git add [-v] <path>...", "git rm"]),
+                   import(".nf\n\\fIgit add\\fR [\\-v] \\\" comment\n    \\fIpath\\fR...\ngit rm\n.fi\n"));
+        assert_eq!(None, super::import(".SH NAME\nls\n"));
+    }
+
+    #[test]
+    fn import_keywords() {
+        // Bold words are keywords, even in capitals, and italic ones
+        // placeholders.
+        assert_eq!(ok(&["dd {if={FILE} | NONE} [<count>] -o{<x>}"]),
+                   import(".B dd\n{\\fBif=\\fIFILE\\fR | \\fBNONE\\fR} [\\fIcount\\fR] \\fB\\-o\\fIx\\fR\n"));
+        let mut forms = super::import(".SH SYNOPSIS\n.B dd NONE\n.I file\n").unwrap();
+        let cmd = forms.remove(0).unwrap().normalize();
+        assert_eq!(Seq(vec![Expr::new(Tok(Text("NONE".to_string())), Span::default()),
+                            Expr::new(Tok(Placeholder("file".to_string())), Span::default())]),
+                   cmd.args.node);
+    }
+
+    #[test]
+    fn import_errors() {
+        // Spans are relative to the page.
+        assert_eq!(vec![Err("6:10: expected `]`, found EOF".to_string())], import(".B ls\n.RB [ \\-a\n"));
+        assert_eq!(vec![Err("5:18: expected `}`, found `]`".to_string())], import("\\fBls\\fR {\\fBa\\fR]\n"));
+    }
+
+    #[test]
+    fn import_generated() {
+        for s in &["ls [-a] [-o FILE] <file>...", "git {add | rm} [FILE]...", "cc --std=STD [--color[=WHEN]]",
+                   "ls {-d DIR}"] {
+            let page = super::synopsis(&[parse(s)]);
+            let forms = super::import(&page).unwrap().into_iter().map(|f| f.unwrap().normalize()).collect::<Vec<_>>();
+            assert_eq!(vec![parse(s)], forms);
+        }
+    }
+}
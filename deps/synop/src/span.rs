@@ -0,0 +1,68 @@
+use std::fmt;
+
+/// A position in the source text.
+///
+/// `offset` is a byte offset, `line` and `column` are 1-origin and counted in
+/// characters.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Pos {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize
+}
+
+impl Default for Pos {
+    fn default() -> Pos { Pos { offset: 0, line: 1, column: 1 } }
+}
+
+impl Pos {
+    /// Returns the position just after `c`.
+    pub fn advance(self, c: char) -> Pos {
+        if c == '\n' {
+            Pos { offset: self.offset + c.len_utf8(), line: self.line + 1, column: 1 }
+        } else {
+            Pos { offset: self.offset + c.len_utf8(), line: self.line, column: self.column + 1 }
+        }
+    }
+
+    /// Returns the position just after `s`.
+    pub fn advance_str(self, s: &str) -> Pos { s.chars().fold(self, Pos::advance) }
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A half-open range `[lo, hi)` of the source text.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub lo: Pos,
+    pub hi: Pos
+}
+
+impl Span {
+    #[inline]
+    pub fn new(lo: Pos, hi: Pos) -> Span { Span { lo, hi } }
+
+    /// Returns an empty span located at `pos`.
+    #[inline]
+    pub fn empty(pos: Pos) -> Span { Span { lo: pos, hi: pos } }
+
+    /// Returns a span covering from the start of `self` to the end of `other`.
+    #[inline]
+    pub fn to(self, other: Span) -> Span { Span { lo: self.lo, hi: other.hi } }
+}
+
+/// A value annotated with its location in the source text.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span
+}
+
+impl<T> Spanned<T> {
+    #[inline]
+    pub fn new(node: T, span: Span) -> Spanned<T> { Spanned { node, span } }
+}
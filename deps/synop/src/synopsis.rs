@@ -0,0 +1,129 @@
+use span::Pos;
+use token::Tokenizer;
+use ast::Command;
+use parser::{self, ParseResult};
+use {mdoc, roff};
+
+/// How the invocation forms of a synopsis are separated.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum FormSeparator {
+    /// Each non-blank line is a form.
+    Line,
+    /// Forms are separated by blank lines, and may span several lines.
+    BlankLine
+}
+
+/// A synopsis document, which lists one or more invocation forms.
+#[derive(Debug)]
+pub struct Synopsis {
+    /// The source text of the whole document.
+    pub src: String,
+    /// The result of parsing each form, in order.  Spans are relative to `src`.
+    pub forms: Vec<ParseResult<Command>>
+}
+
+impl Synopsis {
+    pub fn parse(src: String, sep: FormSeparator) -> Synopsis {
+        let mut forms = vec![];
+        let mut pos = Pos::default();
+        // The start of the form being read, in `FormSeparator::BlankLine` mode.
+        let mut start: Option<Pos> = None;
+        for line in src.split_inclusive('\n') {
+            let line_pos = pos;
+            pos = pos.advance_str(line);
+            let blank = line.trim().is_empty();
+            match sep {
+                FormSeparator::Line => {
+                    if !blank {
+                        forms.push(parse_form(line, line_pos));
+                    }
+                }
+                FormSeparator::BlankLine => {
+                    if blank {
+                        if let Some(start) = start.take() {
+                            forms.push(parse_form(&src[start.offset..line_pos.offset], start));
+                        }
+                    } else if start.is_none() {
+                        start = Some(line_pos);
+                    }
+                }
+            }
+        }
+        if let Some(start) = start {
+            forms.push(parse_form(&src[start.offset..], start));
+        }
+        Synopsis { src, forms }
+    }
+
+    /// Reads the SYNOPSIS section of the man(7) page `page`, as given by
+    /// `roff::import`.  Returns `None` if the page has no SYNOPSIS section.
+    pub fn from_roff(page: &str) -> Option<Synopsis> {
+        roff::import(page).map(|forms| Synopsis { src: page.to_string(), forms })
+    }
+
+    /// Reads the SYNOPSIS section of the mdoc(7) page `page`, as given by
+    /// `mdoc::import`.  Returns `None` if the page has no SYNOPSIS section.
+    pub fn from_mdoc(page: &str) -> Option<Synopsis> {
+        mdoc::import(page).map(|forms| Synopsis { src: page.to_string(), forms })
+    }
+}
+
+fn parse_form(s: &str, pos: Pos) -> ParseResult<Command> {
+    parser::parse_command(Tokenizer::new_at(s.chars(), pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Synopsis, FormSeparator};
+
+    fn pretty(s: &str, sep: FormSeparator) -> Vec<Result<String, String>> {
+        Synopsis::parse(s.to_string(), sep).forms.into_iter()
+            .map(|f| f.map(|e| e.pretty()).map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn line() {
+        assert_eq!(vec![Ok("tar -c FILE...".to_string()), Ok("tar -x".to_string())],
+                   pretty("tar -c FILE...\n\n  tar  -x\n", FormSeparator::Line));
+        assert_eq!(vec![Ok("a".to_string()), Err("expected `]`, found EOF".to_string()),
+                        Ok("c".to_string())],
+                   pretty("a\nb [c\nc", FormSeparator::Line));
+        assert_eq!(Vec::<Result<String, String>>::new(), pretty("\n \n", FormSeparator::Line));
+    }
+
+    #[test]
+    fn blank_line() {
+        assert_eq!(vec![Ok("tar -c FILE...".to_string()), Ok("tar -x [-v]".to_string())],
+                   pretty("\ntar -c\n FILE...\n\n\ntar -x\n[-v]", FormSeparator::BlankLine));
+        assert_eq!(vec![Err("expected `]`, found EOF".to_string()), Ok("c".to_string())],
+                   pretty("a\n[b\n\nc\n", FormSeparator::BlankLine));
+        assert_eq!(vec![Err("expected command name, found `[`".to_string()), Ok("c".to_string())],
+                   pretty("[b]\n\nc\n", FormSeparator::BlankLine));
+    }
+
+    #[test]
+    fn span() {
+        let syn = Synopsis::parse("a\n\n  b [c\n".to_string(), FormSeparator::Line);
+        let err = syn.forms[1].as_ref().unwrap_err();
+        assert_eq!((3, 7), (err.span.lo.line, err.span.lo.column));
+        assert_eq!(9, err.span.lo.offset);
+
+        let syn = Synopsis::parse("a\n\n  b\n [c\n".to_string(), FormSeparator::BlankLine);
+        let form = syn.forms[1].as_ref().unwrap_err();
+        assert_eq!((4, 4), (form.span.lo.line, form.span.lo.column));
+    }
+
+    #[test]
+    fn markup_span() {
+        // Spans point into the page.
+        let page = ".SH SYNOPSIS\n.B cmd\n.RB [ \\-a\n";
+        let syn = Synopsis::from_roff(page).unwrap();
+        let err = syn.forms[0].as_ref().unwrap_err();
+        assert_eq!("\\-a", &syn.src[err.span.lo.offset - 3..err.span.lo.offset]);
+        let page = ".Sh SYNOPSIS\n.Nm cmd\n.Oo Fl a Brc\n";
+        let syn = Synopsis::from_mdoc(page).unwrap();
+        let err = syn.forms[0].as_ref().unwrap_err();
+        assert_eq!("Brc", &syn.src[err.span.lo.offset..err.span.hi.offset]);
+    }
+}
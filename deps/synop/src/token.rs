@@ -1,11 +1,18 @@
-use std::iter::Peekable;
+use std::iter::{self, Peekable};
+use span::{Pos, Span, Spanned};
+use parser::{ParseError, ParseErrorKind};
 use token::Token::*;
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Token {
+    /// A keyword, which is typed literally.
     Text(String),
+    /// A variable, which is substituted by the user.
+    Placeholder(String),
     ShortOpt(String),
     LongOpt(String),
+    /// `=` between an option and its attached argument.
+    Equals,
     LBracket,
     RBracket,
     LBrace,
@@ -15,11 +22,18 @@ pub enum Token {
 }
 
 impl Token {
+    /// Returns true if the token is a `ShortOpt` or a `LongOpt`.
+    pub fn is_option(&self) -> bool { matches!(*self, ShortOpt(_) | LongOpt(_)) }
+
     pub fn pretty(&self) -> String {
         match *self {
             Text(ref s) => s.to_string(),
+            Placeholder(ref s) => {
+                if is_all_caps(s) { s.to_string() } else { format!("<{}>", s) }
+            }
             ShortOpt(ref s) => format!("-{}", s),
             LongOpt(ref s) => format!("--{}", s),
+            Equals   => "=".to_string(),
             LBracket => "[".to_string(),
             RBracket => "]".to_string(),
             LBrace   => "{".to_string(),
@@ -30,65 +44,218 @@ impl Token {
     }
 }
 
-pub struct Tokenizer<T: Iterator<Item = char>> { input: Peekable<T> }
+pub struct Tokenizer<T: Iterator<Item = char>> {
+    input: Peekable<T>,
+    pos: Pos,
+    end: Pos,
+    // Tokens already read from the input, in reverse order.
+    pending: Vec<Result<Spanned<Token>, ParseError>>
+}
 
-fn is_option_char(c: char) -> bool { c.is_alphanumeric() || c == '-' || c == '_' }
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && c != '[' && c != ']' && c != '{' && c != '}' && c != '|'
+}
+
+/// Returns true if `s` looks like a metavariable such as `FILE` or `KEY_2`.
+fn is_all_caps(s: &str) -> bool {
+    let mut cs = s.chars();
+    match cs.next() {
+        Some(c) if c.is_uppercase() => {}
+        _ => return false
+    }
+    cs.all(|c| c.is_uppercase() || c.is_numeric() || c == '_' || c == '-')
+}
+
+/// Removes roff font escapes (`\fB`, `\fI`, `\fR` and `\fP`) from `s` and
+/// replaces `\-` with `-`.  Returns the stripped string and whether it starts
+/// in italic.
+fn strip_roff_escapes(s: &str) -> (String, bool) {
+    let italic = s.starts_with("\\fI");
+    let mut buf = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('\\') {
+        buf.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("\\-") {
+            buf.push('-');
+            rest = &rest[2..];
+        } else if rest.starts_with("\\fB") || rest.starts_with("\\fI") ||
+            rest.starts_with("\\fR") || rest.starts_with("\\fP") {
+            rest = &rest[3..];
+        } else {
+            buf.push('\\');
+            rest = &rest[1..];
+        }
+    }
+    buf.push_str(rest);
+    (buf, italic)
+}
+
+fn word_token(s: &str) -> Token {
+    let (s, italic) = strip_roff_escapes(s);
+    let s = &s[..];
+    if italic {
+        Placeholder(s.to_string())
+    } else if s.len() > 2 && s.starts_with('<') && s.ends_with('>') {
+        Placeholder(s[1..s.len() - 1].to_string())
+    } else if is_all_caps(s) {
+        Placeholder(s.to_string())
+    } else if let Some(name) = s.strip_prefix("--") {
+        LongOpt(name.to_string())
+    } else if let Some(name) = s.strip_prefix('-') {
+        ShortOpt(name.to_string())
+    } else {
+        Text(s.to_string())
+    }
+}
 
 impl<T: Iterator<Item = char>> Tokenizer<T> {
     #[inline]
-    pub fn new(input: T) -> Tokenizer<T> { Tokenizer { input: input.peekable() } }
+    pub fn new(input: T) -> Tokenizer<T> { Tokenizer::new_at(input, Pos::default()) }
+
+    /// Creates a tokenizer for `input` which starts at `pos` of a larger
+    /// source text, so that the spans are relative to that text.
+    pub fn new_at(input: T, pos: Pos) -> Tokenizer<T> {
+        Tokenizer {
+            input: input.peekable(),
+            pos,
+            end: pos,
+            pending: vec![]
+        }
+    }
+
+    /// Returns the position just after the last token read.
+    #[inline]
+    pub fn end(&self) -> Pos { self.end }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.input.next();
+        if let Some(c) = c {
+            self.pos = self.pos.advance(c);
+        }
+        c
+    }
 
     fn push_while<F>(&mut self, buf: &mut String, pred: F)
         where F: Fn(char) -> bool
     {
-        loop {
-            match self.input.peek() {
-                Some(&c) => {
-                    if !pred(c) { break; }
-                    buf.push(c);
-                    self.input.next();
-                },
-                None => break
-            }
+        while let Some(&c) = self.input.peek() {
+            if !pred(c) { break; }
+            buf.push(c);
+            self.bump();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.input.peek() {
+            if !c.is_whitespace() { break; }
+            self.bump();
+        }
+    }
+
+    /// Reads a whitespace-delimited word.  Trailing `...`s are split off as
+    /// `Dots` tokens, any other punctuation is kept in the word.
+    fn read_word(&mut self, lo: Pos) -> Vec<Result<Spanned<Token>, ParseError>> {
+        let mut s = String::new();
+        self.push_while(&mut s, is_word_char);
+
+        let body_len = s.trim_end_matches('.').len();
+        let num_dots = s.len() - body_len;
+        if body_len == 0 && !num_dots.is_multiple_of(3) {
+            let span = Span::new(lo, self.pos);
+            return vec![Err(ParseError::new(ParseErrorKind::InvalidToken(s), span, vec![Dots]))]
+        }
+        let body_len = body_len + num_dots % 3;
+
+        let mut toks = split_word(&s[..body_len], lo).into_iter().map(Ok).collect::<Vec<_>>();
+        let mut dots_lo = lo.advance_str(&s[..body_len]);
+        for _ in 0..(num_dots / 3) {
+            let hi = dots_lo.advance_str("...");
+            toks.push(Ok(Spanned::new(Dots, Span::new(dots_lo, hi))));
+            dots_lo = hi;
         }
+        toks
+    }
+
+    /// Returns the next token without consuming it.
+    pub fn peek(&mut self) -> Option<&Result<Spanned<Token>, ParseError>> {
+        let end = self.end;
+        if let Some(tok) = self.next() {
+            self.pending.push(tok);
+        }
+        self.end = end;
+        self.pending.last()
+    }
+}
+
+/// Splits a word into tokens.  An option with an attached argument
+/// (`--output=FILE`) and an attached argument alone (`=FILE`) are split at the
+/// first `=`.
+fn split_word(s: &str, lo: Pos) -> Vec<Spanned<Token>> {
+    if s.is_empty() {
+        return vec![]
+    }
+    let stripped = strip_roff_escapes(s).0;
+    let eq = if stripped.starts_with('-') || stripped.starts_with('=') { s.find('=') } else { None };
+    let i = match eq {
+        Some(i) => i,
+        None => return vec![Spanned::new(word_token(s), Span::new(lo, lo.advance_str(s)))]
+    };
+
+    let mut toks = vec![];
+    let eq_lo = lo.advance_str(&s[..i]);
+    let eq_hi = eq_lo.advance('=');
+    if i > 0 {
+        toks.push(Spanned::new(word_token(&s[..i]), Span::new(lo, eq_lo)));
+    }
+    toks.push(Spanned::new(Equals, Span::new(eq_lo, eq_hi)));
+    if i + 1 < s.len() {
+        let arg = &s[i + 1..];
+        toks.push(Spanned::new(word_token(arg), Span::new(eq_hi, eq_hi.advance_str(arg))));
+    }
+    toks
+}
+
+impl Tokenizer<iter::Empty<char>> {
+    /// Creates a tokenizer which yields `toks`, tokens read from other markup
+    /// than the plain notation.
+    pub fn from_tokens(toks: Vec<Spanned<Token>>) -> Tokenizer<iter::Empty<char>> {
+        let pos = toks.first().map_or(Pos::default(), |tok| tok.span.lo);
+        let mut tokenizer = Tokenizer::new_at(iter::empty(), pos);
+        tokenizer.pending = toks.into_iter().rev().map(Ok).collect();
+        tokenizer
     }
 }
 
 impl<T: Iterator<Item = char>> Iterator for Tokenizer<T> {
-    type Item = Token;
-
-    fn next(&mut self) -> Option<Token> {
-        match self.input.by_ref().skip_while(|&c| c.is_whitespace()).next() {
-            Some('-') => {
-                let tok = if self.input.peek() == Some(&'-') {
-                    self.input.next();
-                    LongOpt as fn(String) -> Token
-                } else {
-                    ShortOpt as fn(String) -> Token
-                };
-
-                let mut s = String::new();
-                self.push_while(&mut s, is_option_char);
-                Some(tok(s.to_string()))
-            },
-            Some('[') => Some(LBracket),
-            Some(']') => Some(RBracket),
-            Some('{') => Some(LBrace),
-            Some('}') => Some(RBrace),
-            Some('.') => {
-                assert_eq!(Some('.'), self.input.next());
-                assert_eq!(Some('.'), self.input.next());
-                Some(Dots)
-            }
-            Some('|') => Some(Bar),
-            Some(c) => {
-                let mut s = String::new();
-                s.push(c);
-                self.push_while(&mut s, is_option_char);
-                Some(Text(s.to_string()))
-            },
-            None => None
+    type Item = Result<Spanned<Token>, ParseError>;
+
+    fn next(&mut self) -> Option<Result<Spanned<Token>, ParseError>> {
+        if self.pending.is_empty() {
+            self.skip_whitespace();
+            let lo = self.pos;
+            let tok = match self.input.peek() {
+                Some(&'[') => LBracket,
+                Some(&']') => RBracket,
+                Some(&'{') => LBrace,
+                Some(&'}') => RBrace,
+                Some(&'|') => Bar,
+                Some(_) => {
+                    let toks = self.read_word(lo);
+                    self.pending.extend(toks.into_iter().rev());
+                    return self.next()
+                }
+                None => return None
+            };
+            self.bump();
+            self.pending.push(Ok(Spanned::new(tok, Span::new(lo, self.pos))));
+        }
+
+        let tok = self.pending.pop().unwrap();
+        if let Ok(ref tok) = tok {
+            self.end = tok.span.hi;
         }
+        Some(tok)
     }
 }
 
@@ -96,14 +263,16 @@ impl<T: Iterator<Item = char>> Iterator for Tokenizer<T> {
 mod tests {
     use super::{Tokenizer, Token};
     use super::Token::*;
+    use parser::ParseErrorKind;
 
     fn check(output: &[Token], input: &str) {
-        let v = Tokenizer::new(input.chars()).collect::<Vec<_>>();
+        let v = Tokenizer::new(input.chars()).map(|t| t.unwrap().node).collect::<Vec<_>>();
         assert_eq!(output, &v[..]);
     }
     fn short(s: &str) -> Token { ShortOpt(s.to_string()) }
     fn long(s: &str) -> Token { LongOpt(s.to_string()) }
     fn text(s: &str) -> Token { Text(s.to_string()) }
+    fn ph(s: &str) -> Token { Placeholder(s.to_string()) }
 
     #[test]
     fn short_opt() {
@@ -123,6 +292,53 @@ mod tests {
         check(&[long("aaa"), long("bbb"), long("ccc--1_23")], "  --aaa --bbb   --ccc--1_23");
     }
 
+    #[test]
+    fn word() {
+        check(&[text("file.txt"), text("/dev/null"), text("host:port"), text("KEY=VALUE")],
+              "file.txt /dev/null host:port KEY=VALUE");
+        check(&[long("color"), Equals, ph("WHEN"), short("o/x"), text("a.b.c"), text(".b")],
+              "--color=WHEN -o/x a.b.c .b");
+        check(&[text("a...b"), text("a.."), text("a."), Dots], "a...b a.. a....");
+        check(&[text("file"), Dots, Dots, Dots, Dots], "file... ... ......");
+        check(&[LBracket, text("a.b"), Dots, RBracket, short("x"), Dots], "[a.b...]-x...");
+        check(&[short(""), Dots, long(""), Dots], "-... --...");
+    }
+
+    #[test]
+    fn placeholder() {
+        check(&[text("commit"), ph("FILE"), ph("file"), ph("input"), text("<>")],
+              "commit FILE <file> \\fIinput\\fR <>");
+        check(&[ph("A"), ph("KEY_2"), text("Key"), text("KEY=VALUE"), text("_A")],
+              "A KEY_2 Key KEY=VALUE _A");
+        check(&[ph("file"), Dots, ph("FILE"), Dots, ph("dir"), Dots],
+              "<file>... FILE... \\fIdir\\fP...");
+        check(&[text("commit"), short("v"), long("all")],
+              "\\fBcommit\\fR \\fB-v\\fP \\fB\\-\\-all\\fR");
+    }
+
+    #[test]
+    fn attached_arg() {
+        check(&[long("output"), Equals, ph("FILE"), short("D"), Equals, text("a=b")],
+              "--output=FILE -D=a=b");
+        check(&[long("color"), LBracket, Equals, ph("WHEN"), RBracket, Dots],
+              "--color[=WHEN]...");
+        check(&[long("out"), Equals, ph("file"), Equals, long("x"), Equals],
+              "\\fB\\-\\-out\\fR=\\fIfile\\fR = --x=");
+    }
+
+    #[test]
+    fn peek() {
+        let mut tok = Tokenizer::new("-a --b=c".chars());
+        assert_eq!(short("a"), tok.peek().unwrap().clone().unwrap().node);
+        assert_eq!(short("a"), tok.next().unwrap().unwrap().node);
+        assert_eq!(2, tok.end().offset);
+        assert_eq!(long("b"), tok.peek().unwrap().clone().unwrap().node);
+        assert_eq!(2, tok.end().offset);
+        assert_eq!(long("b"), tok.next().unwrap().unwrap().node);
+        assert_eq!(Equals, tok.peek().unwrap().clone().unwrap().node);
+        assert_eq!(6, tok.end().offset);
+    }
+
     #[test]
     fn mixed() {
         check(&[short("a"), LBrace, text("a"), Bar, text("b"), Bar, text("c"), RBrace,
@@ -131,27 +347,56 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn invalid_dots() {
-        for _tok in Tokenizer::new("....".chars()) {}
+        fn check(output: &str, lo: usize, hi: usize, input: &str) {
+            let err = Tokenizer::new(input.chars())
+                .find(|t| t.is_err())
+                .unwrap()
+                .unwrap_err();
+            assert_eq!(ParseErrorKind::InvalidToken(output.to_string()), err.kind);
+            assert_eq!((lo, hi), (err.span.lo.offset, err.span.hi.offset));
+        }
+        check("....", 0, 4, "....");
+        check("..", 0, 2, "..");
+        check(".", 2, 3, "a .");
+        check("..", 3, 5, "a [..]");
+    }
+
+    #[test]
+    fn span() {
+        fn check(output: &[(usize, usize, usize, usize)], input: &str) {
+            let v = Tokenizer::new(input.chars())
+                .map(|t| t.unwrap())
+                .map(|t| (t.span.lo.offset, t.span.hi.offset, t.span.lo.line, t.span.lo.column))
+                .collect::<Vec<_>>();
+            assert_eq!(output, &v[..]);
+        }
+        check(&[(0, 2, 1, 1), (3, 4, 1, 4), (4, 7, 1, 5), (7, 8, 1, 8)], "-a [foo]");
+        check(&[(2, 7, 1, 3), (8, 11, 2, 1)], "  --aaa\n...");
+        check(&[(0, 3, 1, 1), (4, 5, 1, 3)], "\u{3042} |");
+        check(&[(0, 1, 1, 1), (1, 4, 1, 2), (4, 7, 1, 5), (7, 10, 1, 8), (11, 14, 1, 12)],
+              "a......... ...");
     }
 
     #[test]
     fn pretty() {
         fn check(s: &str) {
             let mut tok = Tokenizer::new(s.chars());
-            assert_eq!(s.to_string(), tok.next().unwrap().pretty());
-            assert_eq!(None, tok.next());
+            assert_eq!(s.to_string(), tok.next().unwrap().unwrap().node.pretty());
+            assert!(tok.next().is_none());
         }
         check("a");
         check("b");
         check("-a");
         check("--long");
+        check("FILE");
+        check("<file>");
         check("[");
         check("]");
         check("{");
         check("}");
         check("...");
+        check("=");
         check("|");
     }
 }
@@ -1,14 +1,48 @@
-use std::io::BufReader;
+use std::error::Error;
+use std::fmt;
+use std::io;
 use std::io::prelude::*;
 use super::Tokenizer;
-use ast::Expr;
+use ast::Command;
+use synopsis::{Synopsis, FormSeparator};
+use parser::ParseError;
 
-pub type ReadResult = Result<Expr, String>;
+pub type ReadResult = Result<Command, ReadError>;
 
-pub fn read_ast<R: Read>(reader: R) -> ReadResult {
-    let cs = BufReader::new(reader).chars().map(|c| c.unwrap());
-    match super::parse(Tokenizer::new(cs)) {
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    /// A parse error, with the source text it occurred in.
+    Parse(Box<ParseError>, String)
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadError::Io(ref err) => write!(f, "I/O error: {}", err),
+            ReadError::Parse(ref err, _) => write!(f, "Parse error: {} at {}", err, err.span.lo)
+        }
+    }
+}
+
+impl Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> ReadError { ReadError::Io(err) }
+}
+
+pub fn read_ast<R: Read>(mut reader: R) -> ReadResult {
+    let mut src = String::new();
+    reader.read_to_string(&mut src)?;
+    match super::parse_command(Tokenizer::new(src.chars())) {
         Ok(ast)  => Ok(ast),
-        Err(msg) => Err(format!("Parse error: {}",  msg))
+        Err(err) => Err(ReadError::Parse(Box::new(err), src))
     }
 }
+
+/// Reads a synopsis document, whose forms are separated by `sep`.
+pub fn read_synopses<R: Read>(mut reader: R, sep: FormSeparator) -> io::Result<Synopsis> {
+    let mut src = String::new();
+    reader.read_to_string(&mut src)?;
+    Ok(Synopsis::parse(src, sep))
+}
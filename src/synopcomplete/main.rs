@@ -0,0 +1,59 @@
+#![crate_name = "synopcomplete"]
+#![crate_type = "bin"]
+#![warn(unused, bad_style, unused_qualifications)]
+
+extern crate cmdutil;
+extern crate synop;
+
+use std::io::prelude::*;
+use synop::{Expr, ExprKind, Shell};
+use cmdutil::{Args, CmdSpec, Error, OptSpec};
+
+const SPEC: CmdSpec = CmdSpec {
+    name: "synopcomplete",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Print shell completion scripts for the commands of synopses.",
+    opts: &[
+        OptSpec { short: Some('s'), long: "shell", arg: Some("SHELL"),
+                  help: "complete for SHELL: bash, zsh or fish (default: bash)" },
+        OptSpec { short: Some('u'), long: "unordered", arg: None,
+                  help: "allow runs of optional options in any order" }
+    ]
+};
+
+fn main() {
+    cmdutil::main(|| -> Result<(), Error> {
+        let args = match Args::from_env(&SPEC)? {
+            Some(args) => args,
+            None => return Ok(())
+        };
+        let shell = args.parse_value::<Shell>("shell")?.unwrap_or(Shell::Bash);
+        let unordered = args.flag("unordered");
+
+        // The forms of each command, in the order the commands first appear.
+        let mut commands: Vec<(String, Vec<Expr>)> = vec![];
+        args.for_each_form(|cmd| {
+            let cmd = if unordered { cmd.normalize().infer_unordered() } else { cmd.normalize() };
+            match commands.iter_mut().find(|&&mut (ref name, _)| *name == cmd.name) {
+                Some(&mut (_, ref mut forms)) => forms.push(cmd.args),
+                None => commands.push((cmd.name, vec![cmd.args]))
+            }
+            Ok(())
+        })?;
+
+        let mut out = args.open_output()?;
+        for (i, (name, mut forms)) in commands.into_iter().enumerate() {
+            let expr = if forms.len() == 1 {
+                forms.pop().unwrap()
+            } else {
+                let span = forms[0].span;
+                Expr { node: ExprKind::Select(forms), span }
+            };
+            if i > 0 {
+                writeln!(out)?;
+            }
+            write!(out, "{}", synop::complete::script(shell, &name, &expr)?)?;
+        }
+        Ok(out.flush()?)
+    });
+}
@@ -2,114 +2,110 @@
 #![crate_type = "bin"]
 #![warn(unused, bad_style, unused_qualifications)]
 
-#[cfg(not(test))]
 extern crate cmdutil;
 extern crate synop;
 
-#[cfg(not(test))]
-use std::io;
-use synop::{Token, Expr};
-use synop::Expr::{Tok, Seq, Opt, Repeat, Select};
+use std::io::prelude::*;
+use synop::{Command, ExpandOptions, Word};
+use cmdutil::{Args, CmdSpec, Error, OptSpec};
 
-fn expand(expr: &Expr) -> Vec<Vec<Token>> {
-    match *expr {
-        Tok(ref tok) => vec![vec![tok.clone()]],
-        Seq(ref seq) => {
-            let mut v = vec![vec![]];
-            for ss in seq.iter().map(expand) {
-                let mut v2 = vec![];
-                for s in ss.iter() {
-                    v2.extend(v.iter().cloned().map(|mut x| {
-                        x.extend(s.iter().cloned());
-                        x
-                    }))
-                }
-                v = v2;
-            }
-            v
-        }
-        Opt(ref opt) => {
-            let mut v = expand(&**opt);
-            v.insert(0, vec![]);
-            v
-        }
-        Repeat(ref rep) => {
-            if let Opt(ref rep) = **rep {
-                let mut v = vec![];
-                v.extend(expand(&Seq(vec![])).into_iter());
-                v.extend(expand(&Seq(vec![(**rep).clone()])).into_iter());
-                v.extend(expand(&Seq(vec![(**rep).clone(), (**rep).clone()])).into_iter());
-                v
-            } else {
-                let mut v = vec![];
-                v.extend(expand(&Seq(vec![(**rep).clone()])).into_iter());
-                v.extend(expand(&Seq(vec![(**rep).clone(), (**rep).clone()])).into_iter());
-                v.extend(expand(&Seq(vec![(**rep).clone(), (**rep).clone(), (**rep).clone()])).into_iter());
-                v
-            }
-        }
-        Select(ref sel) => {
-            sel.iter()
-                .map(expand)
-                .flat_map(|e| e.into_iter())
-                .collect()
-        }
+const SPEC: CmdSpec = CmdSpec {
+    name: "synopexpand",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Print the command lines matching synopses.",
+    opts: &[
+        OptSpec { short: Some('u'), long: "unordered", arg: None,
+                  help: "allow runs of optional options in any order" },
+        OptSpec { short: Some('n'), long: "limit", arg: Some("N"),
+                  help: "print at most N command lines for each form" },
+        OptSpec { short: None, long: "budget", arg: Some("N"),
+                  help: "fail if a form would print more than N command lines" },
+        OptSpec { short: None, long: "repeat-min", arg: Some("N"),
+                  help: "repeat `X...` at least N times (default: 1)" },
+        OptSpec { short: None, long: "repeat-max", arg: Some("N"),
+                  help: "repeat `X...` at most N times (default: 3)" },
+        OptSpec { short: None, long: "minimal", arg: None,
+                  help: "repeat `X...` only the least number of times" },
+        OptSpec { short: None, long: "pairwise", arg: None,
+                  help: "print a few command lines covering every pair of choices" },
+        OptSpec { short: Some('t'), long: "strength", arg: Some("T"),
+                  help: "cover every combination of T choices (implies --pairwise)" },
+        OptSpec { short: None, long: "invalid", arg: None,
+                  help: "print command lines which do not match, made by mutating matching ones" }
+    ]
+};
+
+fn print_args<W: Write>(out: &mut W, cmd: &Command, args: &[Word]) -> Result<(), Error> {
+    let mut words = vec![cmd.name.clone()];
+    words.extend(args.iter().map(|w| w.iter().map(|c| c.pretty()).collect::<String>()));
+    writeln!(out, "{}", words.join(" "))?;
+    Ok(())
+}
+
+fn print_expand<W: Write>(out: &mut W, cmd: &Command, opts: &ExpandOptions) -> Result<(), Error> {
+    for args in synop::expand(&cmd.args, opts)? {
+        print_args(out, cmd, &args)?;
+    }
+    Ok(())
+}
+
+fn print_cover<W: Write>(out: &mut W, cmd: &Command, strength: usize, opts: &ExpandOptions) -> Result<(), Error> {
+    let limit = opts.limit.map_or(usize::MAX, |n| n as usize);
+    for args in synop::cover(&cmd.args, strength, opts).iter().take(limit) {
+        print_args(out, cmd, args)?;
     }
+    Ok(())
 }
 
-#[cfg(not(test))]
-fn print_expand(expr: &Expr) {
-    for cmd in expand(expr).iter() {
-        println!("{}", cmd.iter().map(|c| c.pretty()).collect::<Vec<String>>().join(" "));
+fn print_mutants<W: Write>(out: &mut W, cmd: &Command, opts: &ExpandOptions) -> Result<(), Error> {
+    let limit = opts.limit.map_or(usize::MAX, |n| n as usize);
+    for mutant in synop::mutants(&cmd.args, opts).iter().take(limit) {
+        print_args(out, cmd, &mutant.args)?;
     }
+    Ok(())
 }
 
-#[cfg(not(test))]
 fn main() {
-    cmdutil::main(|| {
-        let ast = match synop::read_ast(io::stdin()) {
-            Ok(ast) => ast,
-            Err(s)  => return Err(s)
+    cmdutil::main(|| -> Result<(), Error> {
+        let args = match Args::from_env(&SPEC)? {
+            Some(args) => args,
+            None => return Ok(())
+        };
+        let mut opts = ExpandOptions {
+            limit: args.parse_value("limit")?,
+            budget: args.parse_value("budget")?,
+            ..ExpandOptions::default()
         };
-        match ast.normalize() {
-            Some(e) => print_expand(&e),
-            None => {}
+        if let Some(min) = args.parse_value("repeat-min")? {
+            opts.repeat_min = min;
         }
-        Ok(())
+        if let Some(max) = args.parse_value("repeat-max")? {
+            opts.repeat_max = max;
+        }
+        if args.flag("minimal") {
+            opts.repeat_max = opts.repeat_min;
+        } else if opts.repeat_min > opts.repeat_max {
+            return Err(args.usage_error("`--repeat-min` is greater than `--repeat-max`"))
+        }
+        let strength = match args.parse_value("strength")? {
+            Some(t) => Some(t),
+            None if args.flag("pairwise") => Some(2),
+            None => None
+        };
+        let invalid = args.flag("invalid");
+        if invalid && strength.is_some() {
+            return Err(args.usage_error("`--invalid` cannot be used with `--pairwise`"))
+        }
+        let mut out = args.open_output()?;
+        let unordered = args.flag("unordered");
+        args.for_each_form(|cmd| {
+            let cmd = if unordered { cmd.normalize().infer_unordered() } else { cmd.normalize() };
+            match strength {
+                _ if invalid => print_mutants(&mut out, &cmd, &opts),
+                Some(t) => print_cover(&mut out, &cmd, t, &opts),
+                None => print_expand(&mut out, &cmd, &opts)
+            }
+        })?;
+        Ok(out.flush()?)
     });
 }
-
-#[cfg(test)]
-mod tests {
-    use synop::{Token, Expr};
-    use synop::Token::Text;
-    use synop::Expr::{Tok, Seq, Opt, Repeat, Select};
-
-    fn text_tok(v: Vec<Vec<&str>>) -> Vec<Vec<Token>> {
-        v.into_iter()
-            .map(|x| x.into_iter().map(|s| Text(s.to_string())).collect())
-            .collect()
-    }
-    fn text(s: &str) -> Expr { Tok(Text(s.to_string())) }
-
-    #[test]
-    fn expand() {
-        assert_eq!(text_tok(vec![vec!["a"]]), super::expand(&text("a")));
-        assert_eq!(text_tok(vec![vec!["a", "b"]]),
-                   super::expand(&Seq(vec![text("a"), text("b")])));
-        assert_eq!(text_tok(vec![vec![], vec!["a"]]),
-                   super::expand(&Opt(Box::new(text("a")))));
-        assert_eq!(text_tok(vec![vec!["a"], vec!["a", "a"], vec!["a", "a", "a"]]),
-                   super::expand(&Repeat(Box::new(text("a")))));
-        assert_eq!(text_tok(vec![vec![], vec!["a"], vec!["a", "a"]]),
-                   super::expand(&Repeat(Box::new(Opt(Box::new(text("a")))))));
-        assert_eq!(text_tok(vec![vec!["a"], vec!["b"], vec!["c"]]),
-                   super::expand(&Select(vec![text("a"), text("b"), text("c")])));
-
-        assert_eq!(text_tok(vec![vec!["a", "c"], vec!["b", "c"]]),
-                   super::expand(&Seq(vec![Select(vec![text("a"), text("b")]), text("c")])));
-        assert_eq!(text_tok(vec![vec!["a", "c"], vec!["b", "c"], vec!["a", "d"], vec!["b", "d"]]),
-                   super::expand(&Seq(vec![Select(vec![text("a"), text("b")]),
-                                           Select(vec![text("c"), text("d")])])));
-    }
-}
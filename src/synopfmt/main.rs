@@ -2,25 +2,76 @@
 #![crate_type = "bin"]
 #![warn(unused, bad_style, unused_qualifications)]
 
-#[cfg(not(test))]
 extern crate cmdutil;
-#[cfg(not(test))]
 extern crate synop;
 
-#[cfg(not(test))]
-use std::io;
+use std::io::prelude::*;
+use std::str::FromStr;
+use synop::{mdoc, roff};
+use cmdutil::{Args, CmdSpec, Error, Markup, OptSpec};
+
+const SPEC: CmdSpec = CmdSpec {
+    name: "synopfmt",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Reformat synopses in the canonical form.",
+    opts: &[
+        OptSpec { short: Some('f'), long: "format", arg: Some("FORMAT"),
+                  help: "write synopses as FORMAT: text, roff or mdoc (default: text)" },
+        OptSpec { short: None, long: "from", arg: Some("FORMAT"),
+                  help: "read synopses written as FORMAT: text, roff or mdoc (default: text)" }
+    ]
+};
+
+#[derive(Eq, PartialEq, Clone, Copy)]
+enum Format {
+    Text,
+    Roff,
+    Mdoc
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Format, ()> {
+        match s {
+            "text" => Ok(Format::Text),
+            "roff" => Ok(Format::Roff),
+            "mdoc" => Ok(Format::Mdoc),
+            _ => Err(())
+        }
+    }
+}
 
-#[cfg(not(test))]
 fn main() {
-    cmdutil::main(|| {
-        let ast = match synop::read_ast(io::stdin()) {
-            Ok(ast) => ast,
-            Err(s)  => return Err(s)
+    cmdutil::main(|| -> Result<(), Error> {
+        let mut args = match Args::from_env(&SPEC)? {
+            Some(args) => args,
+            None => return Ok(())
         };
-        match ast.normalize() {
-            Some(x) => println!("{}", x.pretty()),
-            None    => println!("")
-        }
-        Ok(())
+        let format = args.parse_value("format")?.unwrap_or(Format::Text);
+        args.markup = args.parse_value("from")?.unwrap_or(Markup::Plain);
+        let mut out = args.open_output()?;
+        let mut first = true;
+        args.for_each_form(|cmd| {
+            let cmd = cmd.normalize();
+            match format {
+                Format::Text => writeln!(out, "{}", cmd.pretty())?,
+                Format::Roff => {
+                    if first {
+                        writeln!(out, ".SH SYNOPSIS")?;
+                    }
+                    write!(out, "{}", roff::form(&cmd))?;
+                }
+                Format::Mdoc => {
+                    if first {
+                        writeln!(out, ".Sh SYNOPSIS")?;
+                    }
+                    write!(out, "{}", mdoc::form(&cmd))?;
+                }
+            }
+            first = false;
+            Ok(())
+        })?;
+        Ok(out.flush()?)
     });
 }
@@ -0,0 +1,176 @@
+#![crate_name = "synopmatch"]
+#![crate_type = "bin"]
+#![warn(unused, bad_style, unused_qualifications)]
+
+extern crate cmdutil;
+extern crate synop;
+
+use std::io;
+use std::io::prelude::*;
+use synop::{Command, Derivation, Expr};
+use synop::ExprKind::{Tok, OptArg, Seq, Concat, Opt, Repeat, Select, Interleave};
+use cmdutil::{Args, CmdSpec, Error, Input, OptSpec};
+
+const SPEC: CmdSpec = CmdSpec {
+    name: "synopmatch",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Check command lines, one per line and split at whitespace, against synopses.",
+    opts: &[
+        OptSpec { short: Some('u'), long: "unordered", arg: None,
+                  help: "allow runs of optional options in any order" },
+        OptSpec { short: Some('c'), long: "command", arg: Some("LINE"),
+                  help: "check LINE instead of reading command lines from stdin" },
+        OptSpec { short: Some('a'), long: "argv", arg: None,
+                  help: "check the FILE arguments, given after `--`, as one command line" },
+        OptSpec { short: Some('v'), long: "verbose", arg: None,
+                  help: "print how each command line matched" },
+        OptSpec { short: Some('j'), long: "json", arg: None,
+                  help: "print the values bound by each command line as JSON" },
+        OptSpec { short: Some('f'), long: "fast", arg: None,
+                  help: "match with automata compiled from the synopses (no -v or -j)" }
+    ]
+};
+
+// Writes the choices made in `deriv`, one per line.
+fn print_derivation<W: Write>(out: &mut W, expr: &Expr, deriv: &Derivation) -> io::Result<()> {
+    match (&expr.node, deriv) {
+        (Tok(tok), Derivation::Tok(s)) => {
+            if *s != tok.pretty() {
+                writeln!(out, "  {} = {}", tok.pretty(), s)?;
+            }
+        }
+        (OptArg(_, arg, _), Derivation::OptArg(Some(s))) => {
+            writeln!(out, "  {} = {}", arg.pretty(), s)?;
+        }
+        (OptArg(..), Derivation::OptArg(None)) => {}
+        (Seq(seq), Derivation::Seq(ds)) | (Concat(seq), Derivation::Concat(ds)) => {
+            for (e, d) in seq.iter().zip(ds) {
+                print_derivation(out, e, d)?;
+            }
+        }
+        (Opt(e), Derivation::Opt(d)) => {
+            match d {
+                Some(d) => {
+                    writeln!(out, "  {}: taken", expr.pretty())?;
+                    print_derivation(out, e, d)?;
+                }
+                None => writeln!(out, "  {}: not taken", expr.pretty())?
+            }
+        }
+        (Repeat(e), Derivation::Repeat(ds)) => {
+            let plural = if ds.len() == 1 { "" } else { "s" };
+            writeln!(out, "  {}: repeated {} time{}", expr.pretty(), ds.len(), plural)?;
+            for d in ds {
+                print_derivation(out, e, d)?;
+            }
+        }
+        (Select(sel), Derivation::Select(i, d)) => {
+            writeln!(out, "  {{{}}}: `{}`", expr.pretty(), sel[*i].pretty())?;
+            print_derivation(out, &sel[*i], d)?;
+        }
+        (Interleave(xs), Derivation::Interleave(ds)) => {
+            for (i, d) in ds {
+                print_derivation(out, &xs[*i], d)?;
+            }
+        }
+        _ => panic!("derivation does not follow the expression")
+    }
+    Ok(())
+}
+
+fn main() {
+    cmdutil::main(|| -> Result<(), Error> {
+        let mut args = match Args::from_env(&SPEC)? {
+            Some(args) => args,
+            None => return Ok(())
+        };
+        let mut lines = args.values("command").iter()
+            .map(|s| s.split_whitespace().map(|s| s.to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        if args.flag("argv") {
+            // `synopmatch -a -e 'SYNOPSIS' -- "$0" "$@"`
+            lines.push(args.inputs.iter().filter_map(|input| match *input {
+                Input::File(ref arg) => Some(arg.clone()),
+                Input::Stdin => Some("-".to_string()),
+                Input::Expr(_) => None
+            }).collect());
+            args.inputs.retain(|input| matches!(*input, Input::Expr(_)));
+            if args.inputs.is_empty() {
+                return Err(args.usage_error("`--argv` requires the synopsis to be given with `-e`"))
+            }
+        } else if lines.is_empty() {
+            if args.inputs.contains(&Input::Stdin) {
+                let msg = "give the synopsis with `-e` or files, or command lines with `-c`";
+                return Err(args.usage_error(msg))
+            }
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                lines.push(line?.split_whitespace().map(|s| s.to_string()).collect());
+            }
+        }
+
+        let unordered = args.flag("unordered");
+        let mut forms: Vec<Command> = vec![];
+        args.for_each_form(|cmd| {
+            forms.push(if unordered { cmd.normalize().infer_unordered() } else { cmd.normalize() });
+            Ok(())
+        })?;
+
+        let verbose = args.flag("verbose");
+        let json = args.flag("json");
+        if args.flag("fast") && (verbose || json) {
+            return Err(args.usage_error("`--fast` cannot be used with `--verbose` or `--json`"))
+        }
+        let dfas = if args.flag("fast") {
+            // Forms too large to compile are matched by backtracking.
+            forms.iter().map(|cmd| synop::compile(&cmd.args).ok()).collect()
+        } else {
+            vec![]
+        };
+        let mut out = args.open_output()?;
+        let mut total = 0;
+        let mut failed = 0;
+        for argv in lines.iter().filter(|argv| !argv.is_empty()) {
+            total += 1;
+            let line = argv.join(" ");
+            if !dfas.is_empty() {
+                let found = forms.iter().zip(&dfas).find(|&(cmd, dfa)| match *dfa {
+                    Some(ref dfa) => synop::matcher::is_program(&cmd.name, &argv[0]) && dfa.is_match(&argv[1..]),
+                    None => synop::match_command(cmd, argv).is_some()
+                });
+                match found {
+                    Some((cmd, _)) => writeln!(out, "{}: matches `{}`", line, cmd.pretty())?,
+                    None => {
+                        failed += 1;
+                        writeln!(out, "{}: no match", line)?;
+                    }
+                }
+                continue
+            }
+            let found = forms.iter()
+                .filter_map(|cmd| synop::match_command(cmd, argv).map(|d| (cmd, d)))
+                .next();
+            match found {
+                Some((cmd, deriv)) => {
+                    if json {
+                        writeln!(out, "{}", synop::bindings::to_json(&synop::bind(&cmd.args, &deriv)))?;
+                        continue
+                    }
+                    writeln!(out, "{}: matches `{}`", line, cmd.pretty())?;
+                    if verbose {
+                        print_derivation(&mut out, &cmd.args, &deriv)?;
+                    }
+                }
+                None => {
+                    failed += 1;
+                    writeln!(out, "{}", if json { "null".to_string() } else { format!("{}: no match", line) })?;
+                }
+            }
+        }
+        out.flush()?;
+        if failed > 0 {
+            return Err(Error::NoMatch(format!("{} of {} command lines did not match", failed, total)))
+        }
+        Ok(())
+    });
+}
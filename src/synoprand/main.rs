@@ -0,0 +1,93 @@
+#![crate_name = "synoprand"]
+#![crate_type = "bin"]
+#![warn(unused, bad_style, unused_qualifications)]
+
+extern crate cmdutil;
+extern crate synop;
+
+use std::io::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+use synop::{RandomOptions, Rng};
+use cmdutil::{Args, CmdSpec, Error, OptSpec};
+
+const SPEC: CmdSpec = CmdSpec {
+    name: "synoprand",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Print command lines matching synopses, chosen at random.",
+    opts: &[
+        OptSpec { short: Some('u'), long: "unordered", arg: None,
+                  help: "allow runs of optional options in any order" },
+        OptSpec { short: Some('n'), long: "count", arg: Some("N"),
+                  help: "print N command lines for each form (default: 10)" },
+        OptSpec { short: Some('s'), long: "seed", arg: Some("N"),
+                  help: "seed the generator with N (default: the current time)" },
+        OptSpec { short: None, long: "opt-prob", arg: Some("P"),
+                  help: "include `[X]` with probability P (default: 0.5)" },
+        OptSpec { short: None, long: "repeat-prob", arg: Some("P"),
+                  help: "repeat `X...` once more with probability P (default: 0.5)" },
+        OptSpec { short: None, long: "repeat-max", arg: Some("N"),
+                  help: "repeat `X...` at most N times (default: 8)" },
+        OptSpec { short: Some('w'), long: "weight", arg: Some("BRANCH=W"),
+                  help: "choose BRANCH of `{...|...}` with weight W, where others weigh 1" }
+    ]
+};
+
+fn probability(args: &Args, long: &str) -> Result<Option<f64>, Error> {
+    match args.parse_value::<f64>(long)? {
+        Some(p) if !(0.0..=1.0).contains(&p) => {
+            Err(args.usage_error(&format!("`--{}` must be between 0 and 1", long)))
+        }
+        p => Ok(p)
+    }
+}
+
+fn main() {
+    cmdutil::main(|| -> Result<(), Error> {
+        let args = match Args::from_env(&SPEC)? {
+            Some(args) => args,
+            None => return Ok(())
+        };
+        let mut opts = RandomOptions::default();
+        if let Some(p) = probability(&args, "opt-prob")? {
+            opts.opt = p;
+        }
+        if let Some(p) = probability(&args, "repeat-prob")? {
+            opts.repeat = p;
+        }
+        match args.parse_value("repeat-max")? {
+            Some(0) => return Err(args.usage_error("`--repeat-max` must be at least 1")),
+            Some(max) => opts.repeat_max = max,
+            None => {}
+        }
+        for value in args.values("weight") {
+            let (branch, w) = match value.rfind('=') {
+                Some(i) => (&value[..i], value[i + 1..].parse::<f64>().ok()),
+                None => (value, None)
+            };
+            match w {
+                Some(w) if w >= 0.0 && w.is_finite() => { opts.weights.insert(branch.trim().to_string(), w); }
+                _ => return Err(args.invalid_value("weight", value))
+            }
+        }
+        let count = args.parse_value("count")?.unwrap_or(10);
+        let seed = match args.parse_value("seed")? {
+            Some(seed) => seed,
+            None => SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+        };
+        let mut rng = Rng::new(seed);
+
+        let mut out = args.open_output()?;
+        let unordered = args.flag("unordered");
+        args.for_each_form(|cmd| {
+            let cmd = if unordered { cmd.normalize().infer_unordered() } else { cmd.normalize() };
+            for _ in 0..count {
+                let mut words = vec![cmd.name.clone()];
+                words.extend(synop::sample(&cmd.args, &opts, &mut rng).iter()
+                             .map(|w| w.iter().map(|c| c.pretty()).collect::<String>()));
+                writeln!(out, "{}", words.join(" "))?;
+            }
+            Ok(())
+        })?;
+        Ok(out.flush()?)
+    });
+}
@@ -0,0 +1,246 @@
+#![crate_name = "synoptest"]
+#![crate_type = "bin"]
+#![warn(unused, bad_style, unused_qualifications)]
+
+extern crate cmdutil;
+extern crate synop;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use synop::{Command, ExpandOptions, PlaceholderKind, Rng, Token, Word};
+use cmdutil::{Args, CmdSpec, Error, OptSpec};
+
+const SPEC: CmdSpec = CmdSpec {
+    name: "synoptest",
+    version: env!("CARGO_PKG_VERSION"),
+    about: "Run the command lines matching synopses, and report the forms the program rejects.",
+    opts: &[
+        OptSpec { short: Some('x'), long: "exec", arg: Some("PROGRAM"),
+                  help: "run PROGRAM instead of the command named by each form" },
+        OptSpec { short: Some('u'), long: "unordered", arg: None,
+                  help: "allow runs of optional options in any order" },
+        OptSpec { short: Some('n'), long: "limit", arg: Some("N"),
+                  help: "run at most N command lines for each form" },
+        OptSpec { short: None, long: "pairwise", arg: None,
+                  help: "run a few command lines covering every pair of choices" },
+        OptSpec { short: Some('t'), long: "timeout", arg: Some("SECS"),
+                  help: "kill a command line running longer than SECS seconds (default: 5)" },
+        OptSpec { short: Some('s'), long: "status", arg: Some("N"),
+                  help: "accept exit status N as well as 0 (may be repeated)" },
+        OptSpec { short: Some('v'), long: "verbose", arg: None,
+                  help: "print the outcome of every command line" }
+    ]
+};
+
+// Files and directories given for placeholders, in a scratch directory where
+// the command lines run.
+struct Samples {
+    dir: PathBuf
+}
+
+impl Samples {
+    // Creates a new scratch directory.  One which already exists, possibly
+    // made by someone else, is never used since it is removed on drop.
+    fn create() -> io::Result<Samples> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let mut rng = Rng::new(u64::from(process::id()) << 32 | u64::from(nanos));
+        let mut name = format!("synoptest-{}", process::id());
+        for _ in 0..100 {
+            let dir = env::temp_dir().join(&name);
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(Samples { dir }),
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err)
+            }
+            name = format!("synoptest-{}-{:08x}", process::id(), rng.next_u64() as u32);
+        }
+        Err(io::Error::new(io::ErrorKind::AlreadyExists, "cannot create a scratch directory"))
+    }
+
+    fn file(&self) -> PathBuf { self.dir.join("sample.txt") }
+    fn subdir(&self) -> PathBuf { self.dir.join("sample-dir") }
+
+    // Recreates the samples, which the previous command line may have removed
+    // or changed.
+    fn reset(&self) -> io::Result<()> {
+        fs::write(self.file(), "sample\n")?;
+        fs::create_dir_all(self.subdir())
+    }
+
+    fn value(&self, name: &str) -> String {
+        match synop::classify(name) {
+            PlaceholderKind::File => self.file().to_string_lossy().into_owned(),
+            PlaceholderKind::Dir => self.subdir().to_string_lossy().into_owned(),
+            PlaceholderKind::Number => "1".to_string(),
+            PlaceholderKind::Other => "sample".to_string()
+        }
+    }
+
+    // Returns the argument given for `word`.
+    fn arg(&self, word: &[Token]) -> String {
+        word.iter().map(|tok| match *tok {
+            Token::Placeholder(ref name) => self.value(name),
+            ref tok => tok.pretty()
+        }).collect()
+    }
+}
+
+impl Drop for Samples {
+    fn drop(&mut self) { let _ = fs::remove_dir_all(&self.dir); }
+}
+
+enum Outcome {
+    Exited(Option<i32>, String),
+    TimedOut
+}
+
+// Returns the path of `program` to run from another directory: a path
+// containing `/` is made absolute, and a name is looked up in `PATH`.
+fn resolve(program: &str) -> Result<String, Error> {
+    if !program.contains('/') {
+        return Ok(program.to_string())
+    }
+    match fs::canonicalize(program) {
+        Ok(path) => Ok(path.to_string_lossy().into_owned()),
+        Err(err) => Err(Error::Io(io::Error::new(err.kind(), format!("{}: {}", program, err))))
+    }
+}
+
+fn run(program: &str, args: &[String], dir: &Path, timeout: Duration) -> io::Result<Outcome> {
+    let mut child = process::Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Read stderr in another thread, so that a chatty program does not block
+    // on a full pipe.  The thread is never joined: a process left behind by
+    // the program may hold the pipe open past the deadline, in which case
+    // what was read by then is reported.
+    let mut stderr = child.stderr.take().unwrap();
+    let buf = Arc::new(Mutex::new(vec![]));
+    let (tx, rx) = mpsc::channel();
+    {
+        let buf = buf.clone();
+        thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            while let Ok(n) = stderr.read(&mut chunk) {
+                if n == 0 {
+                    break
+                }
+                buf.lock().unwrap().extend_from_slice(&chunk[..n]);
+            }
+            let _ = tx.send(());
+        });
+    }
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            let _ = rx.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+            let stderr = String::from_utf8_lossy(&buf.lock().unwrap()).into_owned();
+            return Ok(Outcome::Exited(status.code(), stderr))
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(Outcome::TimedOut)
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn main() {
+    cmdutil::main(|| -> Result<(), Error> {
+        let args = match Args::from_env(&SPEC)? {
+            Some(args) => args,
+            None => return Ok(())
+        };
+        let opts = ExpandOptions { limit: args.parse_value("limit")?, ..ExpandOptions::default() };
+        let timeout = match args.parse_value::<f64>("timeout")? {
+            Some(secs) if !(secs > 0.0 && secs < 1e9) => {
+                return Err(args.usage_error("`--timeout` must be a positive number of seconds"))
+            }
+            Some(secs) => Duration::from_millis((secs * 1000.0) as u64),
+            None => Duration::from_secs(5)
+        };
+        let mut accepted = vec![0];
+        for status in args.values("status") {
+            accepted.push(status.parse().map_err(|_| args.invalid_value("status", status))?);
+        }
+        let program = args.value("exec").map(|s| s.to_string());
+        let verbose = args.flag("verbose");
+        let pairwise = args.flag("pairwise");
+        let unordered = args.flag("unordered");
+
+        let samples = Samples::create()?;
+        let mut out = args.open_output()?;
+        let mut total = 0;
+        let mut rejected = 0;
+        args.for_each_form(|cmd| {
+            let cmd: Command = if unordered { cmd.normalize().infer_unordered() } else { cmd.normalize() };
+            // Expansions are run as they are generated, not collected first.
+            let lines: Box<dyn Iterator<Item = Vec<Word>>> = if pairwise {
+                let limit = opts.limit.map_or(usize::MAX, |n| n as usize);
+                Box::new(synop::cover(&cmd.args, 2, &opts).into_iter().take(limit))
+            } else {
+                Box::new(synop::expand(&cmd.args, &opts)?)
+            };
+            let program = program.clone().unwrap_or_else(|| cmd.name.clone());
+            // The command lines run in the scratch directory.
+            let path = resolve(&program)?;
+            let mut count = 0;
+            let mut failed = 0;
+            for words in lines {
+                count += 1;
+                let argv = words.iter().map(|w| samples.arg(w)).collect::<Vec<_>>();
+                let line = Some(&program).into_iter().chain(&argv).cloned().collect::<Vec<_>>().join(" ");
+                samples.reset()?;
+                let outcome = match run(&path, &argv, &samples.dir, timeout) {
+                    Ok(outcome) => outcome,
+                    Err(err) => return Err(Error::Io(io::Error::new(err.kind(), format!("{}: {}", program, err))))
+                };
+                let ok = match outcome {
+                    Outcome::Exited(Some(code), _) => accepted.contains(&code),
+                    _ => false
+                };
+                if !ok {
+                    failed += 1;
+                }
+                if ok && !verbose {
+                    continue
+                }
+                match outcome {
+                    Outcome::Exited(Some(code), ref stderr) => {
+                        writeln!(out, "{}: exit status {}", line, code)?;
+                        if !ok {
+                            for l in stderr.lines() {
+                                writeln!(out, "  {}", l)?;
+                            }
+                        }
+                    }
+                    Outcome::Exited(None, _) => writeln!(out, "{}: killed by a signal", line)?,
+                    Outcome::TimedOut => writeln!(out, "{}: timed out", line)?
+                }
+            }
+            total += 1;
+            if failed > 0 {
+                rejected += 1;
+                writeln!(out, "`{}`: {} of {} command lines rejected", cmd.pretty(), failed, count)?;
+            }
+            Ok(())
+        })?;
+        out.flush()?;
+        if rejected > 0 {
+            return Err(Error::NoMatch(format!("{} of {} forms were rejected", rejected, total)))
+        }
+        Ok(())
+    });
+}